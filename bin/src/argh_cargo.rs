@@ -1,15 +1,23 @@
 // From https://gist.github.com/suluke/e0c672492126be0a4f3b4f0e1115d77c
 //! Extend `argh` to be better integrated with the `cargo` ecosystem
 //!
-//! For now, this only adds a --version/-V option which causes early-exit.
+//! This adds a --version/-V option which causes early-exit, and lets a lone `-`
+//! through as a positional argument (see [STDIN]).
 use argh::{FromArgs, TopLevelCommand};
 
+/// What a lone `-` argument is replaced with. argh treats anything starting with
+/// `-` as an option, so it's passed on as a string that can't occur in `argv`.
+pub const STDIN: &str = "\0-";
+
 struct ArgsOrVersion<T: FromArgs>(T);
 impl<T> TopLevelCommand for ArgsOrVersion<T> where T: FromArgs {}
 impl<T> FromArgs for ArgsOrVersion<T>
 where T: FromArgs
 {
     fn from_args(command_name: &[&str], args: &[&str]) -> Result<Self, argh::EarlyExit> {
+        let args: Vec<&str> =
+            args.iter().map(|&arg| if arg == "-" { STDIN } else { arg }).collect();
+        let args = args.as_slice();
         /// Also use argh for catching `--version`-only invocations
         #[derive(FromArgs)]
         struct Version {
@@ -40,12 +48,13 @@ where T: FromArgs
                         Ok(_) => unreachable!(),
                         Err(exit) => exit.output,
                     };
-                    Err(argh::EarlyExit {
-                        output: format!(
-                            "{help}  -V, --version     print version information and exit"
-                        ),
-                        status: Ok(()),
-                    })
+                    // List it with the other options, before any subcommands
+                    let version = "  -V, --version     print version information and exit";
+                    let output = match help.find("\n\nCommands:") {
+                        Some(idx) => format!("{}\n{version}{}", &help[..idx], &help[idx..]),
+                        None => format!("{help}{version}"),
+                    };
+                    Err(argh::EarlyExit { output, status: Ok(()) })
                 }
                 Err(()) => T::from_args(command_name, args).map(Self),
             },
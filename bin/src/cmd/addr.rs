@@ -0,0 +1,271 @@
+use std::{
+    io,
+    io::{stdin, stdout, BufRead, BufWriter, Write},
+};
+
+use argh::FromArgs;
+use cwdemangle::{demangle, parse_map, DemangleOptions, MapEntry};
+
+use crate::{read_input, write_error};
+
+#[derive(FromArgs)]
+/// Resolve addresses to demangled symbol names and offsets.
+#[argh(subcommand, name = "addr")]
+pub struct Args {
+    /// a CodeWarrior or Dolphin map, or a decomp-toolkit symbols.txt
+    #[argh(positional)]
+    symbols: String,
+    /// the addresses to resolve, such as 0x80045A3C. Read from stdin, one per
+    /// line, if none are given
+    #[argh(positional)]
+    addresses: Vec<String>,
+}
+
+/// A symbol's address range and name.
+#[derive(Debug, PartialEq, Eq)]
+struct Symbol {
+    address: u32,
+    size: u32,
+    name: String,
+}
+
+pub fn run(args: &Args, options: &DemangleOptions) -> Result<(), String> {
+    let data = read_input(&args.symbols)?;
+    let symbols = parse_symbols(&String::from_utf8_lossy(&data));
+    if symbols.is_empty() {
+        return Err(format!("No symbols found in {}", args.symbols));
+    }
+
+    let stdout = stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut invalid = 0;
+    let mut resolve = |input: &str| -> Result<(), String> {
+        if !resolve(&mut out, &symbols, input, options).map_err(write_error)? {
+            // Flush first so the diagnostic follows the line it's about
+            out.flush().map_err(write_error)?;
+            eprintln!("Invalid address `{input}`");
+            invalid += 1;
+        }
+        Ok(())
+    };
+    if args.addresses.is_empty() {
+        for line in stdin().lock().lines() {
+            let line = line.map_err(|e| format!("Failed to read stdin: {e}"))?;
+            for address in line.split_whitespace() {
+                resolve(address)?;
+            }
+        }
+    } else {
+        for address in &args.addresses {
+            resolve(address)?;
+        }
+    }
+    out.flush().map_err(write_error)?;
+    if invalid > 0 {
+        return Err(format!("{invalid} invalid address{}", if invalid == 1 { "" } else { "es" }));
+    }
+    Ok(())
+}
+
+/// Reads the symbols from a decomp-toolkit symbols.txt or a map, sorted by
+/// address.
+fn parse_symbols(text: &str) -> Vec<Symbol> {
+    let mut symbols = if text.lines().any(|line| parse_symbols_txt(line).is_some()) {
+        text.lines().filter_map(parse_symbols_txt).collect::<Vec<_>>()
+    } else {
+        parse_map(text)
+            .filter_map(|line| match line.entry {
+                // Skip the section symbols, which span the rest
+                MapEntry::Symbol { virtual_address: Some(address), size, name, .. }
+                    if !name.starts_with('.') && name != "*fill*" =>
+                {
+                    Some(Symbol { address, size, name: name.to_string() })
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    // Of symbols at the same address, prefer the largest
+    symbols.sort_by(|a, b| a.address.cmp(&b.address).then(a.size.cmp(&b.size)));
+    symbols
+}
+
+/// Writes the symbol and offset for the address `input`, or `??` if no symbol
+/// contains it. Returns whether `input` is a valid address.
+fn resolve<W: Write>(
+    out: &mut W,
+    symbols: &[Symbol],
+    input: &str,
+    options: &DemangleOptions,
+) -> io::Result<bool> {
+    let address = match parse_address(input) {
+        Some(address) => address,
+        None => {
+            writeln!(out, "{input}: ??")?;
+            return Ok(false);
+        }
+    };
+    write!(out, "0x{address:08X}: ")?;
+    match lookup(symbols, address) {
+        Some(symbol) => {
+            let name = demangle(&symbol.name, options).unwrap_or_else(|| symbol.name.clone());
+            match address - symbol.address {
+                0 => writeln!(out, "{name}")?,
+                offset => writeln!(out, "{name} + 0x{offset:X}")?,
+            }
+        }
+        None => writeln!(out, "??")?,
+    }
+    Ok(true)
+}
+
+/// Finds the innermost symbol containing `address`, so that an address past an
+/// entry point or local label nested in a function resolves to the function.
+/// Symbols of unknown size are assumed to extend to the next symbol.
+fn lookup(symbols: &[Symbol], address: u32) -> Option<&Symbol> {
+    let idx = symbols.partition_point(|s| s.address <= address).checked_sub(1)?;
+    let symbol = &symbols[idx];
+    if symbol.size == 0 || address - symbol.address < symbol.size {
+        return Some(symbol);
+    }
+    symbols[..idx].iter().rev().find(|s| s.size != 0 && address - s.address < s.size)
+}
+
+fn parse_address(str: &str) -> Option<u32> {
+    let hex = str.strip_prefix("0x").or_else(|| str.strip_prefix("0X")).unwrap_or(str);
+    u32::from_str_radix(hex, 16).ok()
+}
+
+/// Parses a decomp-toolkit symbol line, such as
+/// `Think__6CActorFfR13CStateManager = .text:0x80045A20; // type:function size:0x1C`.
+fn parse_symbols_txt(line: &str) -> Option<Symbol> {
+    let (name, rest) = line.split_once(" = ")?;
+    let (location, comment) = rest.split_once(';')?;
+    let (_, address) = location.rsplit_once(':')?;
+    let address = parse_address(address.trim()).filter(|_| address.trim().starts_with("0x"))?;
+    let size = comment
+        .split_whitespace()
+        .find_map(|attr| attr.strip_prefix("size:"))
+        .and_then(parse_address)
+        .unwrap_or(0);
+    Some(Symbol { address, size, name: name.trim().to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(address: u32, size: u32, name: &str) -> Symbol {
+        Symbol { address, size, name: name.to_string() }
+    }
+
+    fn resolved(symbols: &[Symbol], inputs: &[&str]) -> String {
+        let mut out = Vec::new();
+        for input in inputs {
+            resolve(&mut out, symbols, input, &DemangleOptions::default()).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("0x80045A3C"), Some(0x80045A3C));
+        assert_eq!(parse_address("0X80045a3c"), Some(0x80045A3C));
+        assert_eq!(parse_address("80045a3c"), Some(0x80045A3C));
+        assert_eq!(parse_address("0x"), None);
+        assert_eq!(parse_address("0x100000000"), None);
+        assert_eq!(parse_address("lr"), None);
+        assert_eq!(parse_address(""), None);
+    }
+
+    #[test]
+    fn test_parse_symbols_txt() {
+        assert_eq!(
+            parse_symbols_txt(
+                "Think__6CActorFfR13CStateManager = .text:0x80045A20; // type:function size:0x1C"
+            ),
+            Some(symbol(0x80045A20, 0x1C, "Think__6CActorFfR13CStateManager"))
+        );
+        assert_eq!(
+            parse_symbols_txt("lbl_803E1234 = .data:0x803E1234; // type:object"),
+            Some(symbol(0x803E1234, 0, "lbl_803E1234"))
+        );
+        // The address must be written in hex
+        assert_eq!(parse_symbols_txt("x = .text:80045A20; // size:0x4"), None);
+        assert_eq!(parse_symbols_txt("// comment"), None);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let symbols = parse_symbols(
+            "memset = .text:0x80003100; // type:function size:0x100
+__fill_mem = .text:0x80003140; // type:function size:0x4
+.L_80003150 = .text:0x80003150; // type:label
+Update__6CActorFv = .text:0x80003200; // type:function size:0x10
+next = .text:0x80003300; // type:function
+",
+        );
+        let name = |address| lookup(&symbols, address).map(|s| s.name.as_str());
+        assert_eq!(name(0x80003100), Some("memset"));
+        assert_eq!(name(0x80003143), Some("__fill_mem"));
+        // Past the nested entry point, and past a label of unknown size
+        assert_eq!(name(0x80003144), Some("memset"));
+        assert_eq!(name(0x80003150), Some(".L_80003150"));
+        assert_eq!(name(0x800031FF), Some(".L_80003150"));
+        assert_eq!(name(0x8000320F), Some("Update__6CActorFv"));
+        assert_eq!(name(0x80003210), None);
+        assert_eq!(name(0x800030FF), None);
+        assert_eq!(name(0xFFFFFFFF), Some("next"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let symbols = parse_symbols(
+            "Think__6CActorFfR13CStateManager = .text:0x80045A20; // type:function size:0x1C\n",
+        );
+        assert_eq!(
+            resolved(&symbols, &["0x80045A20", "80045a3b", "0x80045A3C", "pc", "0x80045a24"]),
+            "0x80045A20: CActor::Think(float, CStateManager&)
+0x80045A3B: CActor::Think(float, CStateManager&) + 0x1B
+0x80045A3C: ??
+pc: ??
+0x80045A24: CActor::Think(float, CStateManager&) + 0x4
+"
+        );
+    }
+
+    #[test]
+    fn test_parse_map() {
+        // CodeWarrior
+        let symbols = parse_symbols(
+            ".init section layout
+  Starting        Virtual
+  address  Size   address
+  -----------------------
+  00000000 000144 80003100  4 .init \tos.a __start.c
+  00000000 000140 80003100  4 memset \tRuntime.PPCEABI.H.a mem.c
+  00000040 000004 80003140    __fill_mem (entry of memset) \tRuntime.PPCEABI.H.a mem.c
+  UNUSED   000038 ........ Update__6CActorFv main.o
+",
+        );
+        assert_eq!(symbols, [
+            symbol(0x80003100, 0x140, "memset"),
+            symbol(0x80003140, 4, "__fill_mem")
+        ]);
+        assert_eq!(
+            resolved(&symbols, &["80003142", "80003150"]),
+            "0x80003142: __fill_mem + 0x2\n0x80003150: memset + 0x50\n"
+        );
+        // Dolphin
+        let symbols = parse_symbols(
+            ".text section layout
+80003100 00000144 80003100 0 __start
+80003244 00000010 80003244 0 Update__6CActorFv
+",
+        );
+        assert_eq!(
+            resolved(&symbols, &["80003100", "80003250"]),
+            "0x80003100: __start\n0x80003250: CActor::Update() + 0xC\n"
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    fs, io,
+    io::{stdout, BufWriter, Write},
+};
+
+use argh::FromArgs;
+use cwdemangle::{demangle, DemangleOptions};
+
+use crate::{argh_cargo::STDIN, read_input, write_error};
+
+#[derive(FromArgs)]
+/// Check a corpus of `mangled<TAB>expected` lines against the demangler,
+/// showing each mismatch. Lines with no expected column are expected to fail,
+/// and blank lines and `#` comments are skipped. Exits with an error if any
+/// entry doesn't match.
+#[argh(subcommand, name = "check")]
+pub struct Args {
+    /// the corpus, or `-` for stdin
+    #[argh(positional)]
+    corpus: String,
+    /// rewrite the expected column with the current output instead of checking
+    #[argh(switch)]
+    bless: bool,
+}
+
+/// A corpus line with a symbol on it.
+struct Entry<'a> {
+    line: usize,
+    mangled: &'a str,
+    expected: Option<&'a str>,
+}
+
+pub fn run(args: &Args, options: &DemangleOptions) -> Result<(), String> {
+    let name = if args.corpus == STDIN { "stdin" } else { args.corpus.as_str() };
+    let data = read_input(&args.corpus)?;
+    let text = std::str::from_utf8(&data).map_err(|_| format!("{name} isn't valid UTF-8"))?;
+    if args.bless {
+        if args.corpus == STDIN {
+            return Err("--bless needs a corpus file to rewrite".to_string());
+        }
+        return bless(&args.corpus, text, options);
+    }
+
+    let stdout = stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let (passed, failed) = check(&mut out, name, text, options)
+        .and_then(|r| out.flush().map(|_| r))
+        .map_err(write_error)?;
+    if failed > 0 {
+        return Err(format!("{failed} of {} entries don't match", passed + failed));
+    }
+    Ok(())
+}
+
+/// Checks each entry of the corpus `text`, writing a diff of the mismatches and
+/// a summary. Returns the number of entries that passed and failed.
+fn check<W: Write>(
+    out: &mut W,
+    name: &str,
+    text: &str,
+    options: &DemangleOptions,
+) -> io::Result<(usize, usize)> {
+    let (mut passed, mut failed) = (0, 0);
+    for entry in text.lines().enumerate().filter_map(|(i, line)| parse_entry(i + 1, line)) {
+        let actual = demangle(entry.mangled, options);
+        if actual.as_deref() == entry.expected {
+            passed += 1;
+            continue;
+        }
+        if failed == 0 {
+            writeln!(out, "--- {name} (expected)\n+++ demangled")?;
+        }
+        failed += 1;
+        writeln!(out, "@@ line {}: {} @@", entry.line, entry.mangled)?;
+        let show = |s: Option<&str>| s.unwrap_or("(fails to demangle)").to_string();
+        writeln!(out, "-{}\n+{}", show(entry.expected), show(actual.as_deref()))?;
+    }
+    writeln!(out, "{passed} passed, {failed} failed")?;
+    Ok((passed, failed))
+}
+
+/// Rewrites the expected column of every entry in the corpus at `path`,
+/// keeping comments and blank lines.
+fn bless(path: &str, text: &str, options: &DemangleOptions) -> Result<(), String> {
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let (mut total, mut changed) = (0, 0);
+    let mut blessed = String::with_capacity(text.len());
+    for (i, line) in text.lines().enumerate() {
+        match parse_entry(i + 1, line) {
+            Some(entry) => {
+                let actual = demangle(entry.mangled, options);
+                total += 1;
+                if actual.as_deref() != entry.expected {
+                    changed += 1;
+                }
+                blessed += entry.mangled;
+                if let Some(actual) = actual {
+                    blessed += "\t";
+                    blessed += &actual;
+                }
+            }
+            None => blessed += line,
+        }
+        blessed += newline;
+    }
+    fs::write(path, blessed).map_err(|e| format!("Failed to write {path}: {e}"))?;
+    println!("Updated {changed} of {total} entries in {path}");
+    Ok(())
+}
+
+/// Parses a corpus line, returning `None` for blank lines and comments.
+fn parse_entry(line: usize, text: &str) -> Option<Entry<'_>> {
+    if text.trim().is_empty() || text.starts_with('#') {
+        return None;
+    }
+    let (mangled, expected) = match text.split_once('\t') {
+        Some((mangled, expected)) => (mangled, Some(expected).filter(|e| !e.is_empty())),
+        None => (text, None),
+    };
+    Some(Entry { line, mangled, expected })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CORPUS: &str = "\
+# Functions
+Update__6CActorFv\tCActor::Update()
+
+__ct__3FooFv\tFoo::Foo(void)
+not__mangled
+Draw__6CActorFv
+";
+
+    #[test]
+    fn test_parse_entry() {
+        assert!(parse_entry(1, "").is_none());
+        assert!(parse_entry(1, " \t").is_none());
+        assert!(parse_entry(1, "# Update__6CActorFv\tCActor::Update()").is_none());
+        let entry = parse_entry(3, "Update__6CActorFv\tCActor::Update()").unwrap();
+        assert_eq!(
+            (entry.line, entry.mangled, entry.expected),
+            (3, "Update__6CActorFv", Some("CActor::Update()"))
+        );
+        // No expected column, or an empty one, means the symbol should fail
+        let entry = parse_entry(4, "not__mangled").unwrap();
+        assert_eq!((entry.mangled, entry.expected), ("not__mangled", None));
+        let entry = parse_entry(5, "not__mangled\t").unwrap();
+        assert_eq!((entry.mangled, entry.expected), ("not__mangled", None));
+    }
+
+    #[test]
+    fn test_check() {
+        let mut out = Vec::new();
+        let result = check(&mut out, "corpus.txt", CORPUS, &DemangleOptions::default()).unwrap();
+        assert_eq!(result, (2, 2));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+--- corpus.txt (expected)
++++ demangled
+@@ line 4: __ct__3FooFv @@
+-Foo::Foo(void)
++Foo::Foo()
+@@ line 6: Draw__6CActorFv @@
+-(fails to demangle)
++CActor::Draw()
+2 passed, 2 failed
+"
+        );
+    }
+
+    #[test]
+    fn test_check_passed() {
+        let mut out = Vec::new();
+        let corpus = "Update__6CActorFv\tCActor::Update()\nnot__mangled\n";
+        let result = check(&mut out, "corpus.txt", corpus, &DemangleOptions::default()).unwrap();
+        assert_eq!(result, (2, 0));
+        assert_eq!(String::from_utf8(out).unwrap(), "2 passed, 0 failed\n");
+    }
+
+    #[test]
+    fn test_bless() {
+        let path =
+            std::env::temp_dir().join(format!("cwdemangle-bless-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let corpus = CORPUS.replace('\n', "\r\n");
+        fs::write(path, &corpus).unwrap();
+        let result = bless(path, &corpus, &DemangleOptions::default());
+        let blessed = fs::read_to_string(path);
+        fs::remove_file(path).unwrap();
+        result.unwrap();
+        // Comments, blank lines and line endings are kept
+        assert_eq!(
+            blessed.unwrap(),
+            "# Functions\r
+Update__6CActorFv\tCActor::Update()\r
+\r
+__ct__3FooFv\tFoo::Foo()\r
+not__mangled\r
+Draw__6CActorFv\tCActor::Draw()\r
+"
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use std::io::{stdout, BufWriter, Write};
+
+use argh::FromArgs;
+use cwdemangle::{demangle_parts, DemangleOptions};
+
+use crate::write_error;
+
+#[derive(FromArgs)]
+/// Compare two symbols part by part, such as each of their parameters. Exits
+/// with an error if they differ.
+#[argh(subcommand, name = "diff")]
+pub struct Args {
+    /// the first symbol
+    #[argh(positional)]
+    left: String,
+    /// the second symbol
+    #[argh(positional)]
+    right: String,
+}
+
+pub fn run(args: &Args, options: &DemangleOptions) -> Result<(), String> {
+    let parts = |symbol: &str| {
+        demangle_parts(symbol, options).ok_or_else(|| format!("Failed to demangle {symbol}"))
+    };
+    let (left, right) = (parts(&args.left)?, parts(&args.right)?);
+    let differences = left.diff(&right);
+
+    let stdout = stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    writeln!(out, "- {}", left.demangled).map_err(write_error)?;
+    writeln!(out, "+ {}", right.demangled).map_err(write_error)?;
+    if differences.is_empty() {
+        writeln!(out, "No differences").map_err(write_error)?;
+    }
+    for difference in &differences {
+        writeln!(out, "  {difference}").map_err(write_error)?;
+    }
+    out.flush().map_err(write_error)?;
+    if !differences.is_empty() {
+        return Err("Symbols differ".to_string());
+    }
+    Ok(())
+}
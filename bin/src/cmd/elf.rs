@@ -0,0 +1,86 @@
+use std::io::{stdout, BufWriter, Write};
+
+use argh::FromArgs;
+use cwdemangle::{demangle_bytes, DemangleOptions, Encoding};
+
+use crate::{
+    elf::{is_archive, is_elf, read_archive, read_symbols},
+    read_input, write_error,
+};
+
+#[derive(FromArgs)]
+/// List the symbols of ELF objects and archives with demangled names.
+#[argh(subcommand, name = "elf")]
+pub struct Args {
+    /// ELF objects, executables or `ar` archives, or `-` to read one from stdin
+    #[argh(positional)]
+    files: Vec<String>,
+    /// how non-ASCII bytes in names are shown: utf8 (default), shift-jis or escape
+    #[argh(option, default = "Encoding::Utf8", from_str_fn(parse_encoding))]
+    encoding: Encoding,
+    /// only list symbols defined in the file
+    #[argh(switch)]
+    defined_only: bool,
+}
+
+pub fn parse_encoding(value: &str) -> Result<Encoding, String> {
+    match value {
+        "utf8" | "utf-8" => Ok(Encoding::Utf8),
+        "shift-jis" | "sjis" => Ok(Encoding::ShiftJis),
+        "escape" => Ok(Encoding::Escape),
+        _ => Err(format!("unknown encoding `{value}`, expected utf8, shift-jis or escape")),
+    }
+}
+
+pub fn run(args: &Args, options: &DemangleOptions) -> Result<(), String> {
+    if args.files.is_empty() {
+        return Err("No files given".to_string());
+    }
+    let stdout = stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    for path in &args.files {
+        let data = read_input(path)?;
+        let name = if path == crate::argh_cargo::STDIN { "<stdin>" } else { path };
+        if is_archive(&data) {
+            for (member, data) in read_archive(&data).map_err(|e| format!("{name}: {e}"))? {
+                if is_elf(data) {
+                    writeln!(out, "\n{name}({member}):").map_err(write_error)?;
+                    list(&mut out, args, options, data)
+                        .map_err(|e| format!("{name}({member}): {e}"))?;
+                }
+            }
+        } else {
+            if args.files.len() > 1 {
+                writeln!(out, "\n{name}:").map_err(write_error)?;
+            }
+            list(&mut out, args, options, &data).map_err(|e| format!("{name}: {e}"))?;
+        }
+    }
+    out.flush().map_err(write_error)
+}
+
+/// Writes a line for each symbol in an ELF file.
+fn list<W: Write>(
+    out: &mut W,
+    args: &Args,
+    options: &DemangleOptions,
+    data: &[u8],
+) -> Result<(), String> {
+    for symbol in read_symbols(data)? {
+        if args.defined_only && symbol.section == b"UND" {
+            continue;
+        }
+        let name = demangle_bytes(symbol.name, args.encoding, options)
+            .unwrap_or_else(|| String::from_utf8_lossy(symbol.name).into_owned());
+        writeln!(
+            out,
+            "{:08x} {:08x} {:<12} {:<6} {name}",
+            symbol.value,
+            symbol.size,
+            String::from_utf8_lossy(symbol.section),
+            symbol.binding,
+        )
+        .map_err(write_error)?;
+    }
+    Ok(())
+}
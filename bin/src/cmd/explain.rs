@@ -0,0 +1,76 @@
+use std::io::{stdout, BufWriter, Write};
+
+use argh::FromArgs;
+use cwdemangle::{explain, DemangleOptions};
+
+use crate::write_error;
+
+#[derive(FromArgs)]
+/// Show the steps taken to demangle a symbol, and where parsing failed.
+#[argh(subcommand, name = "explain")]
+pub struct Args {
+    /// the symbols to explain
+    #[argh(positional)]
+    symbols: Vec<String>,
+}
+
+pub fn run(args: &Args, options: &DemangleOptions) -> Result<(), String> {
+    let stdout = stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    for (i, symbol) in args.symbols.iter().enumerate() {
+        if i > 0 {
+            writeln!(out).map_err(write_error)?;
+        }
+        write_explanation(&mut out, symbol, options).map_err(write_error)?;
+    }
+    out.flush().map_err(write_error)
+}
+
+pub fn write_explanation<W: Write>(
+    out: &mut W,
+    symbol: &str,
+    options: &DemangleOptions,
+) -> std::io::Result<()> {
+    let explanation = explain(symbol, options);
+    writeln!(out, "{symbol}")?;
+    for step in &explanation.steps {
+        let input = symbol.get(step.input.clone()).unwrap_or_default();
+        write!(
+            out,
+            "{:indent$}{} [{}..{}] `{input}`",
+            "",
+            step.kind.description(),
+            step.input.start,
+            step.input.end,
+            indent = 2 + step.depth * 2
+        )?;
+        match &step.output {
+            Some(output) if output.is_empty() => writeln!(out)?,
+            Some(output) => writeln!(out, " => {output}")?,
+            None => writeln!(out, " (failed)")?,
+        }
+    }
+    match &explanation.demangled {
+        Some(demangled) => writeln!(out, "=> {demangled}"),
+        None => {
+            // The failure furthest into the symbol is usually the one that matters
+            let failed = explanation
+                .steps
+                .iter()
+                .filter(|step| step.output.is_none())
+                .max_by_key(|step| step.input.start);
+            writeln!(out, "Failed to demangle")?;
+            if let Some(step) = failed {
+                writeln!(out, "  {symbol}")?;
+                writeln!(
+                    out,
+                    "  {:>1$} {2} failed here",
+                    "^",
+                    step.input.start + 1,
+                    step.kind.description()
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
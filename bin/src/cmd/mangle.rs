@@ -0,0 +1,55 @@
+use std::io::{stdin, stdout, BufRead, BufWriter, Write};
+
+use argh::FromArgs;
+use cwdemangle::{mangle, DemangleOptions};
+
+use crate::write_error;
+
+#[derive(FromArgs)]
+/// Mangle C++ declarations, written the way they're demangled, such as
+/// `CActor::SetActive(bool)`.
+#[argh(subcommand, name = "mangle")]
+pub struct Args {
+    /// the declarations to mangle. Read from stdin, one per line, if none are
+    /// given
+    #[argh(positional)]
+    declarations: Vec<String>,
+}
+
+pub fn run(args: &Args, _options: &DemangleOptions) -> Result<(), String> {
+    let stdout = stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let (mut total, mut failed) = (0, 0);
+    let mut mangle_one = |declaration: &str| -> Result<(), String> {
+        total += 1;
+        match mangle(declaration) {
+            Ok(mangled) => writeln!(out, "{mangled}").map_err(write_error),
+            Err(e) => {
+                failed += 1;
+                // Point at the character, not the byte
+                let column = declaration[..e.position].chars().count();
+                eprintln!("Failed to mangle: {}", e.message);
+                eprintln!("  {declaration}");
+                eprintln!("  {:>1$}", "^", column + 1);
+                Ok(())
+            }
+        }
+    };
+    if args.declarations.is_empty() {
+        for line in stdin().lock().lines() {
+            let line = line.map_err(|e| format!("Failed to read stdin: {e}"))?;
+            if !line.trim().is_empty() {
+                mangle_one(line.trim_end())?;
+            }
+        }
+    } else {
+        for declaration in &args.declarations {
+            mangle_one(declaration)?;
+        }
+    }
+    out.flush().map_err(write_error)?;
+    if failed > 0 {
+        return Err(format!("{failed} of {total} declarations failed to mangle"));
+    }
+    Ok(())
+}
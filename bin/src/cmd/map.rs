@@ -0,0 +1,118 @@
+use std::{
+    io,
+    io::{stdout, BufWriter, Write},
+};
+
+use argh::FromArgs;
+use cwdemangle::{demangle_bytes, parse_map, DemangleOptions, Encoding};
+
+use super::elf::parse_encoding;
+use crate::{read_input, write_error};
+
+#[derive(FromArgs)]
+/// Rewrite a CodeWarrior linker map with demangled symbol names.
+#[argh(subcommand, name = "map")]
+pub struct Args {
+    /// the map file, or `-` to read it from stdin
+    #[argh(positional)]
+    file: String,
+    /// keep the mangled names, adding the demangled name to the end of each line
+    #[argh(switch)]
+    extra_column: bool,
+    /// how non-ASCII bytes in names are shown: utf8 (default), shift-jis or escape
+    #[argh(option, default = "Encoding::Utf8", from_str_fn(parse_encoding))]
+    encoding: Encoding,
+}
+
+pub fn run(args: &Args, options: &DemangleOptions) -> Result<(), String> {
+    let data = read_input(&args.file)?;
+    let stdout = stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    rewrite(&mut out, &data, args, options).and_then(|_| out.flush()).map_err(write_error)
+}
+
+/// Writes `data` with its symbol names demangled, copying every other byte as is.
+fn rewrite<W: Write>(
+    out: &mut W,
+    data: &[u8],
+    args: &Args,
+    options: &DemangleOptions,
+) -> io::Result<()> {
+    // Object and symbol names may be in another encoding. The layout of a map
+    // is ASCII, so it's parsed with each other byte masked, which keeps the
+    // offsets of the names the same as in `data`
+    let masked: String = data.iter().map(|&c| if c.is_ascii() { c as char } else { '?' }).collect();
+    for line in parse_map(&masked) {
+        let start = line.text.as_ptr() as usize - masked.as_ptr() as usize;
+        let text = &data[start..start + line.text.len() + line.newline.len()];
+        let (text, newline) = text.split_at(line.text.len());
+        let mut end = 0;
+        let mut extra = None;
+        for range in line.name_ranges() {
+            let demangled = match demangle_bytes(&text[range.clone()], args.encoding, options) {
+                Some(demangled) => demangled,
+                None => continue,
+            };
+            if args.extra_column {
+                extra.get_or_insert(demangled);
+            } else {
+                out.write_all(&text[end..range.start])?;
+                out.write_all(demangled.as_bytes())?;
+                end = range.end;
+            }
+        }
+        out.write_all(&text[end..])?;
+        if let Some(demangled) = extra {
+            write!(out, "\t{demangled}")?;
+        }
+        out.write_all(newline)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewritten(data: &[u8], extra_column: bool, encoding: Encoding) -> Vec<u8> {
+        let args = Args { file: String::new(), extra_column, encoding };
+        let mut out = Vec::new();
+        rewrite(&mut out, data, &args, &DemangleOptions::default()).unwrap();
+        out
+    }
+
+    /// A section layout whose object names, and one symbol name, are in CP932.
+    const MAP: &[u8] = b".text section layout\r
+  Starting        Virtual\r
+  address  Size   address\r
+  -----------------------\r
+  00000000 000010 80004000  4 Update__6CActorFv \t\x83\x65\x83\x58\x83\x67.o \r
+  00000010 000010 80004010  4 Draw__6\x83\x65\x83\x58\x83\x67Fv \tmain.o \r
+  00000020 000010 80004020  4 not__mangled \t\x93\xfa\x96\x7b.o";
+
+    #[test]
+    fn test_rewrite() {
+        assert_eq!(
+            rewritten(MAP, false, Encoding::Utf8),
+            b".text section layout\r
+  Starting        Virtual\r
+  address  Size   address\r
+  -----------------------\r
+  00000000 000010 80004000  4 CActor::Update() \t\x83\x65\x83\x58\x83\x67.o \r
+  00000010 000010 80004010  4 \\x83e\\x83X\\x83g::Draw() \tmain.o \r
+  00000020 000010 80004020  4 not__mangled \t\x93\xfa\x96\x7b.o"
+                .to_vec()
+        );
+        // The demangled names are added in UTF-8, and the rest is left as it was
+        let out = rewritten(MAP, true, Encoding::ShiftJis);
+        let mut expected =
+            b"  00000000 000010 80004000  4 Update__6CActorFv \t\x83\x65\x83\x58\x83\x67.o "
+                .to_vec();
+        expected.extend(b"\tCActor::Update()\r\n");
+        expected
+            .extend(b"  00000010 000010 80004010  4 Draw__6\x83\x65\x83\x58\x83\x67Fv \tmain.o ");
+        expected.extend("\tテスト::Draw()\r\n".as_bytes());
+        expected.extend(b"  00000020 000010 80004020  4 not__mangled \t\x93\xfa\x96\x7b.o");
+        assert_eq!(out[out.len() - expected.len()..], expected);
+    }
+}
@@ -0,0 +1,47 @@
+//! Subcommands, for working with files other than plain lists of symbols.
+
+use argh::FromArgs;
+use cwdemangle::DemangleOptions;
+
+pub mod addr;
+pub mod check;
+pub mod diff;
+pub mod elf;
+pub mod explain;
+pub mod mangle;
+pub mod map;
+pub mod repl;
+pub mod search;
+pub mod stats;
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+pub enum Command {
+    Addr(addr::Args),
+    Check(check::Args),
+    Diff(diff::Args),
+    Elf(elf::Args),
+    Explain(explain::Args),
+    Mangle(mangle::Args),
+    Map(map::Args),
+    Repl(repl::Args),
+    Search(search::Args),
+    Stats(stats::Args),
+}
+
+impl Command {
+    pub fn run(&self, options: &DemangleOptions) -> Result<(), String> {
+        match self {
+            Command::Addr(args) => addr::run(args, options),
+            Command::Check(args) => check::run(args, options),
+            Command::Diff(args) => diff::run(args, options),
+            Command::Elf(args) => elf::run(args, options),
+            Command::Explain(args) => explain::run(args, options),
+            Command::Mangle(args) => mangle::run(args, options),
+            Command::Map(args) => map::run(args, options),
+            Command::Repl(args) => repl::run(args, options),
+            Command::Search(args) => search::run(args, options),
+            Command::Stats(args) => stats::run(args, options),
+        }
+    }
+}
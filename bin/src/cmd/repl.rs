@@ -0,0 +1,190 @@
+use std::io::{stdout, Write};
+
+use argh::FromArgs;
+use cwdemangle::{demangle, demangle_parts, mangle, DemangleOptions};
+
+use crate::{
+    cmd::explain::write_explanation,
+    editor::{Editor, RawMode},
+    write_error,
+};
+
+#[derive(FromArgs)]
+/// Demangle symbols interactively. Enter `:help` for commands.
+#[argh(subcommand, name = "repl")]
+pub struct Args {}
+
+const HELP: &str = "\
+Enter a symbol to demangle it. Commands act on the last symbol entered, or on
+the symbol or declaration after them:
+  :explain [symbol]       show the steps taken to demangle it
+  :mangle [declaration]   mangle a declaration, or the last symbol's demangled
+                          name
+  :kind [symbol]          show what kind of entity it names
+  :set [option [on|off]]  show the options, or set or toggle one
+  :history                show the lines entered this session
+  :help                   show this help
+  :quit                   exit (or Ctrl-D)";
+
+pub fn run(_args: &Args, options: &DemangleOptions) -> Result<(), String> {
+    let mut options = *options;
+    // Raw mode stays on for the whole session, and is restored on any return
+    let raw_mode = RawMode::enable();
+    let mut editor = Editor::new(raw_mode.is_some());
+    let mut last = None::<String>;
+    while let Some(line) = editor.read_line("> ").map_err(|e| format!("Failed to read: {e}"))? {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let output = match line.strip_prefix(':') {
+            Some(command) => {
+                let (command, arg) = command.split_once(' ').unwrap_or((command, ""));
+                let arg = Some(arg.trim()).filter(|arg| !arg.is_empty());
+                match command {
+                    "q" | "quit" | "exit" => break,
+                    "h" | "help" => HELP.to_string(),
+                    "history" => editor
+                        .history()
+                        .iter()
+                        .enumerate()
+                        .map(|(i, line)| format!("{:>4}  {line}", i + 1))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    "set" => set(&mut options, arg),
+                    _ => match arg.or(last.as_deref()) {
+                        None => "Enter a symbol first".to_string(),
+                        Some(symbol) => match command {
+                            "explain" => {
+                                let mut out = Vec::new();
+                                write_explanation(&mut out, symbol, &options)
+                                    .map_err(write_error)?;
+                                String::from_utf8_lossy(&out).trim_end().to_string()
+                            }
+                            "mangle" => mangle_symbol(symbol, &options),
+                            "kind" => match demangle_parts(symbol, &options) {
+                                Some(parts) => parts.kind.as_str().to_string(),
+                                None => "Failed to demangle".to_string(),
+                            },
+                            _ => {
+                                format!("Unknown command `:{command}`, enter `:help` for commands")
+                            }
+                        },
+                    },
+                }
+            }
+            None => {
+                last = Some(line.to_string());
+                demangle(line, &options).unwrap_or_else(|| "Failed to demangle".to_string())
+            }
+        };
+        let stdout = stdout();
+        let mut out = stdout.lock();
+        writeln!(out, "{output}").and_then(|_| out.flush()).map_err(write_error)?;
+    }
+    Ok(())
+}
+
+/// Shows the options, or sets one as given by `arg`, such as `mw_extensions on`.
+/// Without a value, the option is toggled.
+fn set(options: &mut DemangleOptions, arg: Option<&str>) -> String {
+    let (name, value) = match arg {
+        Some(arg) => arg.split_once(' ').map_or((arg, None), |(n, v)| (n, Some(v.trim()))),
+        None => {
+            return format!(
+                "omit_empty_parameters = {}\nmw_extensions = {}",
+                options.omit_empty_parameters, options.mw_extensions
+            )
+        }
+    };
+    let (name, field) = match name.replace('-', "_").as_str() {
+        "omit_empty_parameters" => ("omit_empty_parameters", &mut options.omit_empty_parameters),
+        "mw_extensions" => ("mw_extensions", &mut options.mw_extensions),
+        _ => {
+            return format!(
+                "Unknown option `{name}`, expected omit_empty_parameters or mw_extensions"
+            )
+        }
+    };
+    *field = match value {
+        None => !*field,
+        Some("on" | "true" | "1") => true,
+        Some("off" | "false" | "0") => false,
+        Some(value) => return format!("Invalid value `{value}`, expected on or off"),
+    };
+    format!("{name} = {field}")
+}
+
+/// Mangles `symbol` if it's a declaration, or else its demangled name, noting if
+/// that doesn't give back the symbol.
+fn mangle_symbol(symbol: &str, options: &DemangleOptions) -> String {
+    let demangled = demangle(symbol, options);
+    let declaration = demangled.as_deref().unwrap_or(symbol);
+    match mangle(declaration) {
+        Ok(mangled) if demangled.is_some() && mangled != symbol => {
+            format!("{mangled}\n(differs from the symbol entered)")
+        }
+        Ok(mangled) => mangled,
+        Err(e) => {
+            let column = declaration[..e.position].chars().count();
+            format!("Failed to mangle: {}\n  {declaration}\n  {:>2$}", e.message, "^", column + 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set() {
+        let mut options = DemangleOptions::default();
+        assert_eq!(set(&mut options, None), "omit_empty_parameters = true\nmw_extensions = false");
+        assert_eq!(set(&mut options, Some("mw_extensions")), "mw_extensions = true");
+        assert!(options.mw_extensions);
+        assert_eq!(set(&mut options, Some("mw-extensions")), "mw_extensions = false");
+        assert!(!options.mw_extensions);
+        assert_eq!(
+            set(&mut options, Some("omit-empty-parameters off")),
+            "omit_empty_parameters = false"
+        );
+        assert_eq!(
+            set(&mut options, Some("omit_empty_parameters  1")),
+            "omit_empty_parameters = true"
+        );
+        assert!(options.omit_empty_parameters);
+    }
+
+    #[test]
+    fn test_set_invalid() {
+        let mut options = DemangleOptions::default();
+        assert_eq!(
+            set(&mut options, Some("verbose on")),
+            "Unknown option `verbose`, expected omit_empty_parameters or mw_extensions"
+        );
+        assert_eq!(
+            set(&mut options, Some("mw_extensions maybe")),
+            "Invalid value `maybe`, expected on or off"
+        );
+        assert!(!options.mw_extensions);
+    }
+
+    #[test]
+    fn test_mangle_symbol() {
+        let options = DemangleOptions::default();
+        // A symbol mangles back to itself
+        assert_eq!(mangle_symbol("Update__6CActorFv", &options), "Update__6CActorFv");
+        // A declaration is mangled as is
+        assert_eq!(mangle_symbol("CActor::Update()", &options), "Update__6CActorFv");
+        // A single qualified name is mangled without the `Q`
+        assert_eq!(
+            mangle_symbol("foo__Q13BarFv", &options),
+            "foo__3BarFv\n(differs from the symbol entered)"
+        );
+        // The caret points at where mangling failed, counting chars
+        assert_eq!(
+            mangle_symbol("テスト::(", &options),
+            "Failed to mangle: expected a name\n  テスト::(\n       ^"
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use std::io::{stdout, BufWriter, Write};
+
+use argh::FromArgs;
+use cwdemangle::{demangle_parts, DemangleOptions, Parts, SymbolKind};
+
+use crate::{argh_cargo::STDIN, pattern::Pattern, symbols::read_symbols, write_error};
+
+#[derive(FromArgs)]
+/// Find symbols by their demangled names, printing the mangled and demangled
+/// name of each match. Every filter given must match. Exits with an error if
+/// nothing matches.
+#[argh(subcommand, name = "search")]
+pub struct Args {
+    /// symbol lists, CodeWarrior maps, or ELF objects or archives, or `-` for
+    /// stdin. Stdin is read if none are given
+    #[argh(positional)]
+    files: Vec<String>,
+    /// a glob the whole demangled name must match, such as `CActor::*(*float*)`
+    #[argh(option)]
+    glob: Option<String>,
+    /// a regular expression to find in the demangled name
+    #[argh(option)]
+    regex: Option<String>,
+    /// a glob the class path must match, such as `CActor` or `rstl::vector<*>`
+    #[argh(option)]
+    class: Option<String>,
+    /// a glob the base name must match, such as `Think` or `__*`
+    #[argh(option)]
+    name: Option<String>,
+    /// text a parameter type must contain, such as `CStateManager&` (may be
+    /// repeated)
+    #[argh(option)]
+    param: Vec<String>,
+    /// only match const member functions
+    #[argh(switch)]
+    r#const: bool,
+    /// only match functions that aren't const member functions
+    #[argh(switch)]
+    non_const: bool,
+    /// the kinds of symbol to match, such as function, constructor or variable
+    /// (may be repeated)
+    #[argh(option, from_str_fn(parse_kind))]
+    kind: Vec<SymbolKind>,
+}
+
+fn parse_kind(value: &str) -> Result<SymbolKind, String> {
+    const KINDS: &[SymbolKind] = &[
+        SymbolKind::Function,
+        SymbolKind::Constructor,
+        SymbolKind::Destructor,
+        SymbolKind::Operator,
+        SymbolKind::Conversion,
+        SymbolKind::Special,
+        SymbolKind::Variable,
+        SymbolKind::LocalStatic,
+        SymbolKind::StaticInitializer,
+        SymbolKind::StaticDestructor,
+    ];
+    KINDS.iter().copied().find(|kind| kind.as_str() == value).ok_or_else(|| {
+        let names = KINDS.iter().map(|kind| kind.as_str()).collect::<Vec<_>>();
+        format!("unknown kind `{value}`, expected one of {}", names.join(", "))
+    })
+}
+
+/// The compiled filters.
+struct Filter<'a> {
+    args: &'a Args,
+    glob: Option<Pattern>,
+    regex: Option<Pattern>,
+    class: Option<Pattern>,
+    name: Option<Pattern>,
+}
+
+impl Filter<'_> {
+    fn new(args: &Args) -> Result<Filter<'_>, String> {
+        let compile = |pattern: &Option<String>, flag: &str, regex: bool| {
+            pattern
+                .as_deref()
+                .map(|pattern| if regex { Pattern::regex(pattern) } else { Pattern::glob(pattern) })
+                .transpose()
+                .map_err(|e| format!("Invalid {flag} `{}`: {e}", pattern.as_deref().unwrap_or("")))
+        };
+        Ok(Filter {
+            args,
+            glob: compile(&args.glob, "--glob", false)?,
+            regex: compile(&args.regex, "--regex", true)?,
+            class: compile(&args.class, "--class", false)?,
+            name: compile(&args.name, "--name", false)?,
+        })
+    }
+
+    fn matches(&self, parts: &Parts) -> bool {
+        let args = self.args;
+        let matches = |pattern: &Option<Pattern>, text: &str| {
+            pattern.as_ref().map_or(true, |pattern| pattern.is_match(text))
+        };
+        let parameters = parts.parameters.as_deref().unwrap_or_default();
+        matches(&self.glob, &parts.demangled)
+            && matches(&self.regex, &parts.demangled)
+            && matches(&self.class, &parts.class_path.join("::"))
+            && matches(&self.name, &parts.name)
+            && args.param.iter().all(|param| parameters.iter().any(|p| p.contains(param.as_str())))
+            && (!args.r#const || parts.is_const)
+            && (!args.non_const || (parts.parameters.is_some() && !parts.is_const))
+            && (args.kind.is_empty() || args.kind.contains(&parts.kind))
+    }
+}
+
+pub fn run(args: &Args, options: &DemangleOptions) -> Result<(), String> {
+    if args.r#const && args.non_const {
+        return Err("--const and --non-const can't be used together".to_string());
+    }
+    let filter = Filter::new(args)?;
+
+    let stdout = stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut found = false;
+    let files = if args.files.is_empty() { vec![STDIN.to_string()] } else { args.files.clone() };
+    for path in &files {
+        for symbol in read_symbols(path)? {
+            let parts = match demangle_parts(&symbol, options) {
+                Some(parts) => parts,
+                None => continue,
+            };
+            if filter.matches(&parts) {
+                found = true;
+                writeln!(out, "{symbol}\t{}", parts.demangled).map_err(write_error)?;
+            }
+        }
+    }
+    out.flush().map_err(write_error)?;
+    if !found {
+        return Err("No symbols matched".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYMBOLS: &[&str] = &[
+        "Think__6CActorFfR13CStateManager",
+        "Update__6CActorFf",
+        "GetPos__6CActorCFv",
+        "__ct__6CActorFv",
+        "gCount__6CActor",
+        "size__Q24rstl36vector<i,Q24rstl17rmemory_allocator>CFv",
+        "clear__Q24rstl36vector<i,Q24rstl17rmemory_allocator>Fv",
+    ];
+
+    /// The symbols matched by the filters in `args`.
+    fn search(args: &[&str]) -> Vec<&'static str> {
+        let args = <Args as FromArgs>::from_args(&["search"], args).unwrap();
+        let filter = Filter::new(&args).unwrap();
+        let options = DemangleOptions::default();
+        SYMBOLS
+            .iter()
+            .copied()
+            .filter(|symbol| filter.matches(&demangle_parts(symbol, &options).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_kind() {
+        assert_eq!(parse_kind("function"), Ok(SymbolKind::Function));
+        assert_eq!(parse_kind("local_static"), Ok(SymbolKind::LocalStatic));
+        let error = parse_kind("method").unwrap_err();
+        assert!(error.starts_with("unknown kind `method`, expected one of function, constructor,"));
+    }
+
+    #[test]
+    fn test_param() {
+        assert_eq!(search(&["--param", "CStateManager"]), ["Think__6CActorFfR13CStateManager"]);
+        assert_eq!(search(&["--param", "float"]), [
+            "Think__6CActorFfR13CStateManager",
+            "Update__6CActorFf"
+        ]);
+        // Every parameter given must be found
+        assert_eq!(search(&["--param", "float", "--param", "CStateManager&"]), [
+            "Think__6CActorFfR13CStateManager"
+        ]);
+        assert!(search(&["--param", "float", "--param", "int"]).is_empty());
+    }
+
+    #[test]
+    fn test_const() {
+        assert_eq!(search(&["--const"]), [
+            "GetPos__6CActorCFv",
+            "size__Q24rstl36vector<i,Q24rstl17rmemory_allocator>CFv"
+        ]);
+        // Variables have no parameters, so they're neither const nor non-const
+        // member functions
+        assert_eq!(search(&["--non-const", "--class", "CActor"]), [
+            "Think__6CActorFfR13CStateManager",
+            "Update__6CActorFf",
+            "__ct__6CActorFv"
+        ]);
+    }
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(search(&["--kind", "constructor", "--kind", "variable"]), [
+            "__ct__6CActorFv",
+            "gCount__6CActor"
+        ]);
+        assert_eq!(search(&["--kind", "variable", "--name", "Update"]), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_class() {
+        assert_eq!(search(&["--class", "rstl::vector<*>"]), [
+            "size__Q24rstl36vector<i,Q24rstl17rmemory_allocator>CFv",
+            "clear__Q24rstl36vector<i,Q24rstl17rmemory_allocator>Fv"
+        ]);
+        assert_eq!(search(&["--class", "rstl::vector<int, *>", "--non-const"]), [
+            "clear__Q24rstl36vector<i,Q24rstl17rmemory_allocator>Fv"
+        ]);
+        // The glob must match the whole class path
+        assert!(search(&["--class", "vector<*>"]).is_empty());
+    }
+}
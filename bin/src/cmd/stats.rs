@@ -0,0 +1,367 @@
+use std::{
+    collections::HashMap,
+    io,
+    io::{stdout, BufWriter, Write},
+};
+
+use argh::FromArgs;
+use cwdemangle::{demangle_parts, DemangleOptions};
+
+use crate::{
+    argh_cargo::STDIN,
+    format::{write_json_opt, write_json_str, Format},
+    symbols::read_symbols,
+    write_error,
+};
+
+#[derive(FromArgs)]
+/// Summarise a symbol table: the symbols owned by each namespace and class,
+/// the symbol kinds, the most instantiated templates and the longest symbols.
+#[argh(subcommand, name = "stats")]
+pub struct Args {
+    /// symbol lists, CodeWarrior maps, or ELF objects or archives, or `-` for
+    /// stdin. Stdin is read if none are given
+    #[argh(positional)]
+    files: Vec<String>,
+    /// output format: text (default) or json
+    #[argh(option, default = "Format::Text", from_str_fn(parse_format))]
+    format: Format,
+    /// how many entries to show in each list (default 10)
+    #[argh(option, default = "10")]
+    top: usize,
+}
+
+fn parse_format(value: &str) -> Result<Format, String> {
+    match value.parse()? {
+        format @ (Format::Text | Format::Json) => Ok(format),
+        _ => Err(format!("unknown format `{value}`, expected text or json")),
+    }
+}
+
+/// The instantiations of a template.
+#[derive(Default)]
+struct Template {
+    /// The number of symbols in any instantiation.
+    symbols: usize,
+    /// The number of symbols in each instantiation.
+    instances: HashMap<String, usize>,
+}
+
+/// A template's name, its instantiations, and the largest of them.
+type TopTemplate<'a> = (&'a str, &'a Template, Vec<(&'a str, usize)>);
+
+#[derive(Default)]
+struct Stats {
+    total: usize,
+    failed: usize,
+    kinds: HashMap<&'static str, usize>,
+    namespaces: HashMap<String, usize>,
+    classes: HashMap<String, usize>,
+    templates: HashMap<String, Template>,
+    /// The length, mangled and demangled names of every symbol.
+    lengths: Vec<(usize, String, Option<String>)>,
+}
+
+pub fn run(args: &Args, options: &DemangleOptions) -> Result<(), String> {
+    let mut stats = Stats::default();
+    let files = if args.files.is_empty() { vec![STDIN.to_string()] } else { args.files.clone() };
+    for path in &files {
+        for symbol in read_symbols(path)? {
+            stats.add(symbol, options);
+        }
+    }
+    let stdout = stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    match args.format {
+        Format::Json => stats.write_json(&mut out, args.top),
+        _ => stats.write_text(&mut out, args.top),
+    }
+    .and_then(|_| out.flush())
+    .map_err(write_error)
+}
+
+impl Stats {
+    fn add(&mut self, symbol: String, options: &DemangleOptions) {
+        self.total += 1;
+        let parts = match demangle_parts(&symbol, options) {
+            Some(parts) => parts,
+            None => {
+                self.failed += 1;
+                self.lengths.push((symbol.len(), symbol, None));
+                return;
+            }
+        };
+        *self.kinds.entry(parts.kind.as_str()).or_default() += 1;
+        // CodeWarrior mangles namespaces and classes alike, so namespaces are
+        // counted by the outermost scope and classes by the innermost
+        let namespace = parts.class_path.first().map_or("(global)", String::as_str);
+        *self.namespaces.entry(namespace.to_string()).or_default() += 1;
+        if !parts.class_path.is_empty() {
+            *self.classes.entry(parts.class_path.join("::")).or_default() += 1;
+        }
+        // Each scope that's a template instantiation, and the name itself for
+        // function templates
+        let components = parts.class_path.iter().chain(Some(&parts.name)).collect::<Vec<_>>();
+        let mut template = String::new();
+        for (i, component) in components.iter().enumerate() {
+            if i > 0 {
+                template += "::";
+            }
+            match template_name(component) {
+                Some(name) => {
+                    template += name;
+                    let entry = self.templates.entry(template.clone()).or_default();
+                    entry.symbols += 1;
+                    let instance = components[..=i].iter().map(|c| c.as_str()).collect::<Vec<_>>();
+                    *entry.instances.entry(instance.join("::")).or_default() += 1;
+                }
+                None => template += component,
+            }
+        }
+        self.lengths.push((symbol.len(), symbol, Some(parts.demangled)));
+    }
+
+    /// The templates with the most symbols, and the largest instantiations of each.
+    fn top_templates(&self, top: usize) -> Vec<TopTemplate<'_>> {
+        let symbols = self.templates.iter().map(|(name, t)| (name, t.symbols)).collect();
+        top_counts(&symbols, top)
+            .into_iter()
+            .filter_map(|(name, _)| self.templates.get_key_value(name))
+            .map(|(name, template)| (name.as_str(), template, top_counts(&template.instances, top)))
+            .collect()
+    }
+
+    fn longest(&self, top: usize) -> Vec<&(usize, String, Option<String>)> {
+        let mut longest = self.lengths.iter().collect::<Vec<_>>();
+        longest.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        longest.truncate(top);
+        longest
+    }
+
+    fn write_text<W: Write>(&self, out: &mut W, top: usize) -> io::Result<()> {
+        writeln!(out, "{} symbols, {} failed to demangle", self.total, self.failed)?;
+        write_counts(out, "Kinds", &top_counts(&self.kinds, usize::MAX))?;
+        write_counts(out, "Namespaces", &top_counts(&self.namespaces, top))?;
+        write_counts(out, "Classes", &top_counts(&self.classes, top))?;
+        writeln!(out, "\nTemplates:")?;
+        for (name, template, instances) in self.top_templates(top) {
+            let count = template.instances.len();
+            writeln!(
+                out,
+                "  {:>6}  {name} ({count} instantiation{})",
+                template.symbols,
+                if count == 1 { "" } else { "s" }
+            )?;
+            for (instance, symbols) in instances {
+                writeln!(out, "    {symbols:>6}  {instance}")?;
+            }
+        }
+        writeln!(out, "\nLongest symbols:")?;
+        for (len, mangled, demangled) in self.longest(top) {
+            writeln!(out, "  {len:>6}  {}", demangled.as_ref().unwrap_or(mangled))?;
+        }
+        Ok(())
+    }
+
+    fn write_json<W: Write>(&self, out: &mut W, top: usize) -> io::Result<()> {
+        write!(out, "{{\"total\":{},\"failed\":{},\"kinds\":", self.total, self.failed)?;
+        write_json_counts(out, &top_counts(&self.kinds, usize::MAX))?;
+        write!(out, ",\"namespaces\":")?;
+        write_json_counts(out, &top_counts(&self.namespaces, top))?;
+        write!(out, ",\"classes\":")?;
+        write_json_counts(out, &top_counts(&self.classes, top))?;
+        write!(out, ",\"templates\":[")?;
+        for (i, (name, template, instances)) in self.top_templates(top).into_iter().enumerate() {
+            write!(out, "{}{{\"name\":", if i == 0 { "" } else { "," })?;
+            write_json_str(out, name)?;
+            write!(
+                out,
+                ",\"count\":{},\"instantiation_count\":{},\"instantiations\":",
+                template.symbols,
+                template.instances.len()
+            )?;
+            write_json_counts(out, &instances)?;
+            write!(out, "}}")?;
+        }
+        write!(out, "],\"longest\":[")?;
+        for (i, (len, mangled, demangled)) in self.longest(top).into_iter().enumerate() {
+            write!(out, "{}{{\"mangled\":", if i == 0 { "" } else { "," })?;
+            write_json_str(out, mangled)?;
+            write!(out, ",\"demangled\":")?;
+            write_json_opt(out, demangled.as_deref())?;
+            write!(out, ",\"length\":{len}}}")?;
+        }
+        writeln!(out, "]}}")
+    }
+}
+
+/// Returns the template's name if `component` of a qualified name is a
+/// template instantiation, such as `vector` for `vector<int>`.
+fn template_name(component: &str) -> Option<&str> {
+    // Operators such as `operator<` aren't templates
+    if component.starts_with("operator") || !component.ends_with('>') {
+        return None;
+    }
+    component.find('<').map(|i| &component[..i])
+}
+
+/// The `top` largest counts, largest first, then by name.
+fn top_counts<K: AsRef<str>>(counts: &HashMap<K, usize>, top: usize) -> Vec<(&str, usize)> {
+    let mut counts = counts.iter().map(|(k, &v)| (k.as_ref(), v)).collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    counts.truncate(top);
+    counts
+}
+
+fn write_counts<W: Write>(out: &mut W, title: &str, counts: &[(&str, usize)]) -> io::Result<()> {
+    writeln!(out, "\n{title}:")?;
+    for (name, count) in counts {
+        writeln!(out, "  {count:>6}  {name}")?;
+    }
+    Ok(())
+}
+
+fn write_json_counts<W: Write>(out: &mut W, counts: &[(&str, usize)]) -> io::Result<()> {
+    write!(out, "[")?;
+    for (i, (name, count)) in counts.iter().enumerate() {
+        write!(out, "{}{{\"name\":", if i == 0 { "" } else { "," })?;
+        write_json_str(out, name)?;
+        write!(out, ",\"count\":{count}}}")?;
+    }
+    write!(out, "]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYMBOLS: &[&str] = &[
+        "size__Q24rstl36vector<i,Q24rstl17rmemory_allocator>CFv",
+        "size__Q24rstl36vector<f,Q24rstl17rmemory_allocator>CFv",
+        "clear__Q24rstl80vector<Q24rstl36vector<i,Q24rstl17rmemory_allocator>,Q24rstl17rmemory_allocator>Fv",
+        "__lt__FRC3FooRC3Foo",
+        "max<i>__FRCiRCi",
+        "Update__6CActorFv",
+        "not__mangled",
+    ];
+
+    fn stats() -> Stats {
+        let mut stats = Stats::default();
+        for symbol in SYMBOLS {
+            stats.add(symbol.to_string(), &DemangleOptions::default());
+        }
+        stats
+    }
+
+    #[test]
+    fn test_template_name() {
+        assert_eq!(template_name("vector<int>"), Some("vector"));
+        assert_eq!(template_name("vector<vector<int>, allocator<int>>"), Some("vector"));
+        assert_eq!(template_name("vector"), None);
+        // Operators that contain or end with `<` or `>` aren't templates
+        assert_eq!(template_name("operator<"), None);
+        assert_eq!(template_name("operator>"), None);
+        assert_eq!(template_name("operator->"), None);
+        assert_eq!(template_name("operator>>"), None);
+    }
+
+    #[test]
+    fn test_top_counts() {
+        let counts = HashMap::from([("b", 2), ("c", 1), ("a", 2), ("d", 3)]);
+        // Largest first, then by name
+        assert_eq!(top_counts(&counts, usize::MAX), [("d", 3), ("a", 2), ("b", 2), ("c", 1)]);
+        assert_eq!(top_counts(&counts, 2), [("d", 3), ("a", 2)]);
+        assert_eq!(top_counts(&counts, 0), []);
+    }
+
+    #[test]
+    fn test_add() {
+        let stats = stats();
+        assert_eq!((stats.total, stats.failed), (7, 1));
+        assert_eq!(top_counts(&stats.kinds, usize::MAX), [("function", 5), ("operator", 1)]);
+        assert_eq!(top_counts(&stats.namespaces, usize::MAX), [
+            ("rstl", 3),
+            ("(global)", 2),
+            ("CActor", 1)
+        ]);
+        assert_eq!(top_counts(&stats.classes, usize::MAX), [
+            ("CActor", 1),
+            ("rstl::vector<float, rstl::rmemory_allocator>", 1),
+            ("rstl::vector<int, rstl::rmemory_allocator>", 1),
+            (
+                "rstl::vector<rstl::vector<int, rstl::rmemory_allocator>, rstl::rmemory_allocator>",
+                1
+            ),
+        ]);
+        // The instantiations of `rstl::vector` are grouped under one template,
+        // and `operator<` isn't taken for one
+        let templates = stats
+            .top_templates(usize::MAX)
+            .into_iter()
+            .map(|(name, template, instances)| (name, template.symbols, instances))
+            .collect::<Vec<_>>();
+        assert_eq!(templates, [
+            ("rstl::vector", 3, vec![
+                ("rstl::vector<float, rstl::rmemory_allocator>", 1),
+                ("rstl::vector<int, rstl::rmemory_allocator>", 1),
+                (
+                    "rstl::vector<rstl::vector<int, rstl::rmemory_allocator>, rstl::rmemory_allocator>",
+                    1
+                ),
+            ]),
+            ("max", 1, vec![("max<int>", 1)]),
+        ]);
+        // Failed symbols are still counted by length, under their mangled name
+        assert!(stats.lengths.contains(&(12, "not__mangled".to_string(), None)));
+    }
+
+    #[test]
+    fn test_write_text() {
+        let mut out = Vec::new();
+        stats().write_text(&mut out, 1).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+7 symbols, 1 failed to demangle
+
+Kinds:
+       5  function
+       1  operator
+
+Namespaces:
+       3  rstl
+
+Classes:
+       1  CActor
+
+Templates:
+       3  rstl::vector (3 instantiations)
+         1  rstl::vector<float, rstl::rmemory_allocator>
+
+Longest symbols:
+      98  rstl::vector<rstl::vector<int, rstl::rmemory_allocator>, rstl::rmemory_allocator>::clear()
+"
+        );
+    }
+
+    #[test]
+    fn test_write_json() {
+        let mut out = Vec::new();
+        stats().write_json(&mut out, 1).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                r#"{"total":7,"failed":1,"#,
+                r#""kinds":[{"name":"function","count":5},{"name":"operator","count":1}],"#,
+                r#""namespaces":[{"name":"rstl","count":3}],"#,
+                r#""classes":[{"name":"CActor","count":1}],"#,
+                r#""templates":[{"name":"rstl::vector","count":3,"instantiation_count":3,"#,
+                r#""instantiations":[{"name":"rstl::vector<float, rstl::rmemory_allocator>","count":1}]}],"#,
+                r#""longest":[{"mangled":"clear__Q24rstl80vector<Q24rstl36vector<i,Q24rstl17rmemory_allocator>,Q24rstl17rmemory_allocator>Fv","#,
+                r#""demangled":"rstl::vector<rstl::vector<int, rstl::rmemory_allocator>, rstl::rmemory_allocator>::clear()","#,
+                r#""length":98}]}"#,
+                "\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! ANSI colours for demangled names, chosen by the kind of each token.
+
+use std::str::FromStr;
+
+use cwdemangle::{TokenKind, Tokens};
+
+/// When to colour output, for `--color`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Colour output if stdout is a terminal.
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("unknown color choice `{s}`, expected auto, always or never")),
+        }
+    }
+}
+
+impl ColorChoice {
+    /// Whether output should be coloured. For `auto`, this follows the
+    /// `NO_COLOR` and `TERM` conventions.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").map_or(true, |v| v.is_empty())
+                    && std::env::var_os("TERM").map_or(true, |term| term != "dumb")
+                    && stdout_is_terminal()
+            }
+        }
+    }
+}
+
+/// Whether stdout is a character device, which terminals are, unlike files
+/// and pipes. Other character devices such as `/dev/null` count too, so
+/// `--color auto` colours output that's thrown away. A real `isatty` check
+/// needs `std::io::IsTerminal`, which is newer than the supported Rust version,
+/// or a dependency.
+#[cfg(unix)]
+fn stdout_is_terminal() -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    std::fs::metadata("/dev/stdout").map_or(false, |m| m.file_type().is_char_device())
+}
+
+#[cfg(not(unix))]
+fn stdout_is_terminal() -> bool { false }
+
+/// The SGR parameters for a kind of token, or `None` to leave it uncoloured.
+fn style(kind: TokenKind) -> Option<&'static str> {
+    match kind {
+        TokenKind::Namespace => Some("36"),
+        TokenKind::Class => Some("33"),
+        TokenKind::Function => Some("1;32"),
+        TokenKind::Variable => Some("32"),
+        TokenKind::Keyword => Some("35"),
+        TokenKind::BuiltinType => Some("34"),
+        TokenKind::Literal => Some("31"),
+        TokenKind::Annotation => Some("2"),
+        TokenKind::Punctuation | TokenKind::Whitespace => None,
+    }
+}
+
+/// Returns the demangled name with each token coloured by its kind.
+pub fn paint(tokens: &Tokens) -> String {
+    let mut out = String::with_capacity(tokens.demangled.len() * 2);
+    for (kind, text) in tokens.iter() {
+        match style(kind) {
+            Some(style) => {
+                out += "\x1b[";
+                out += style;
+                out += "m";
+                out += text;
+                out += "\x1b[0m";
+            }
+            None => out += text,
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use cwdemangle::{demangle_tokens, DemangleOptions};
+
+    use super::*;
+
+    #[test]
+    fn test_color_choice() {
+        assert!(matches!("auto".parse(), Ok(ColorChoice::Auto)));
+        assert!(matches!("always".parse(), Ok(ColorChoice::Always)));
+        assert!(matches!("never".parse(), Ok(ColorChoice::Never)));
+        assert_eq!(
+            "Always".parse::<ColorChoice>().err().as_deref(),
+            Some("unknown color choice `Always`, expected auto, always or never")
+        );
+        assert!(ColorChoice::Always.enabled());
+        assert!(!ColorChoice::Never.enabled());
+    }
+
+    #[test]
+    fn test_paint() {
+        let tokens =
+            demangle_tokens("Think__6CActorFfR13CStateManager", &DemangleOptions::default())
+                .unwrap();
+        assert_eq!(
+            paint(&tokens),
+            "\x1b[33mCActor\x1b[0m::\x1b[1;32mThink\x1b[0m(\x1b[34mfloat\x1b[0m, \
+             \x1b[33mCStateManager\x1b[0m&)"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! A personality compatible with `c++filt` and `llvm-cxxfilt`, for build scripts
+//! and tools that expect one. It's selected by running the binary as `cwfilt`,
+//! or by passing `--cxxfilt` as the first argument.
+
+use std::{
+    io::{stdin, stdout, BufRead, BufWriter, Write},
+    iter::once,
+    path::Path,
+};
+
+use cwdemangle::{demangle_parts, demangle_type_name, DemangleOptions};
+
+use crate::{filter, write_error};
+
+const HELP: &str = "\
+OVERVIEW: CodeWarrior C++ symbol demangler, compatible with llvm-cxxfilt
+
+USAGE: cwfilt [options] <mangled>...
+
+Mangled names are read from stdin if none are given, and replaced wherever they
+appear in each line.
+
+OPTIONS:
+  --format=<value>       Ignored; only CodeWarrior mangling is supported
+  -s <value>             Alias for --format
+  --help                 Display available options
+  -h                     Alias for --help
+  --no-params            Strip function parameters and qualifiers
+  -p                     Alias for --no-params
+  --no-strip-underscore  Do not strip the leading underscore (default)
+  -n                     Alias for --no-strip-underscore
+  --strip-underscore     Strip the leading underscore
+  -_                     Alias for --strip-underscore
+  --types                Attempt to demangle types as well as symbol names
+  -t                     Alias for --types
+  --version              Display the version of this program";
+
+#[derive(Default)]
+struct Args {
+    strip_underscore: bool,
+    no_params: bool,
+    types: bool,
+    symbols: Vec<String>,
+}
+
+/// Returns the arguments for the personality if it was selected by `argv`.
+pub fn selected_args(argv: &[String]) -> Option<&[String]> {
+    let name = argv.first().and_then(|arg| Path::new(arg).file_stem());
+    if name.map_or(false, |name| name.eq_ignore_ascii_case("cwfilt")) {
+        Some(&argv[1..])
+    } else if argv.get(1).map_or(false, |arg| arg == "--cxxfilt") {
+        Some(&argv[2..])
+    } else {
+        None
+    }
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let args = match parse_args(args)? {
+        Some(args) => args,
+        None => return Ok(()),
+    };
+    let options = DemangleOptions::default();
+    let stdout = stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if args.symbols.is_empty() {
+        let demangle = |symbol: &[u8]| {
+            std::str::from_utf8(symbol).ok().and_then(|s| demangle_word(&args, &options, s))
+        };
+        let stdin = stdin();
+        let mut reader = stdin.lock();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => return Err(format!("Failed to read stdin: {e}")),
+            }
+            filter(&mut out, &line, &demangle).and_then(|_| out.flush()).map_err(write_error)?;
+        }
+    } else {
+        // Arguments are demangled whole, like llvm-cxxfilt
+        for symbol in &args.symbols {
+            let demangled = demangle_word(&args, &options, symbol);
+            writeln!(out, "{}", demangled.as_deref().unwrap_or(symbol)).map_err(write_error)?;
+        }
+    }
+    out.flush().map_err(write_error)
+}
+
+/// Parses the arguments, returning `None` if they asked for help or the
+/// version, which has been printed.
+fn parse_args(args: &[String]) -> Result<Option<Args>, String> {
+    let mut parsed = Args::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            parsed.symbols.extend(iter.cloned());
+            break;
+        }
+        let flags = if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = long.split_once('=').map_or((long, None), |(n, v)| (n, Some(v)));
+            let flag = match name {
+                "format" => 's',
+                "help" => 'h',
+                "no-params" => 'p',
+                "no-strip-underscore" => 'n',
+                "strip-underscore" => '_',
+                "types" => 't',
+                "version" => {
+                    println!("cwfilt {}", env!("CARGO_PKG_VERSION"));
+                    return Ok(None);
+                }
+                _ => return Err(format!("unknown argument '{arg}'")),
+            };
+            if flag == 's' {
+                if value.is_none() && iter.next().is_none() {
+                    return Err(format!("no value given for '{arg}'"));
+                }
+                continue;
+            }
+            if value.is_some() {
+                return Err(format!("'--{name}' doesn't take a value"));
+            }
+            flag.to_string()
+        } else if let Some(short) = arg.strip_prefix('-').filter(|s| !s.is_empty()) {
+            short.to_string()
+        } else {
+            parsed.symbols.push(arg.clone());
+            continue;
+        };
+        // Short flags may be combined, as in `-np`
+        for (i, flag) in flags.char_indices() {
+            match flag {
+                '_' => parsed.strip_underscore = true,
+                'n' => parsed.strip_underscore = false,
+                'p' => parsed.no_params = true,
+                't' => parsed.types = true,
+                'h' => {
+                    println!("{HELP}");
+                    return Ok(None);
+                }
+                // The value is the rest of the argument, or the next one
+                's' => {
+                    if i + 1 == flags.len() && iter.next().is_none() {
+                        return Err(format!("no value given for '{arg}'"));
+                    }
+                    break;
+                }
+                _ => return Err(format!("unknown argument '{arg}'")),
+            }
+        }
+    }
+    Ok(Some(parsed))
+}
+
+/// Demangles a symbol, or a type if `--types` was given, returning `None` if it
+/// can't be demangled.
+fn demangle_word(args: &Args, options: &DemangleOptions, word: &str) -> Option<String> {
+    let word = match word.strip_prefix('_') {
+        Some(stripped) if args.strip_underscore => stripped,
+        _ => word,
+    };
+    match demangle_parts(word, options) {
+        Some(parts) if args.no_params && parts.parameters.is_some() => Some(
+            parts
+                .class_path
+                .iter()
+                .chain(once(&parts.name))
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("::"),
+        ),
+        Some(parts) => Some(parts.demangled),
+        None if args.types => demangle_type_name(word, options),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> { args.iter().map(|arg| arg.to_string()).collect() }
+
+    /// The flags strip_underscore, no_params and types, and the symbols.
+    type Parsed = (bool, bool, bool, Vec<String>);
+
+    fn parsed(args: &[&str]) -> Result<Option<Parsed>, String> {
+        Ok(parse_args(&strings(args))?
+            .map(|a| (a.strip_underscore, a.no_params, a.types, a.symbols)))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let symbols = |args: &[&str]| Some((false, false, false, strings(args)));
+        assert_eq!(parsed(&[]), Ok(symbols(&[])));
+        assert_eq!(parsed(&["a__Fv", "b__Fv"]), Ok(symbols(&["a__Fv", "b__Fv"])));
+        assert_eq!(parsed(&["-_", "a"]), Ok(Some((true, false, false, strings(&["a"])))));
+        assert_eq!(parsed(&["-_", "-n"]), Ok(symbols(&[])));
+        assert_eq!(parsed(&["-p"]), Ok(Some((false, true, false, vec![]))));
+        assert_eq!(parsed(&["-t"]), Ok(Some((false, false, true, vec![]))));
+        assert_eq!(parsed(&["-_pt"]), Ok(Some((true, true, true, vec![]))));
+        assert_eq!(
+            parsed(&["--strip-underscore", "--no-params", "--types"]),
+            Ok(Some((true, true, true, vec![])))
+        );
+        assert_eq!(parsed(&["--", "-p", "--types"]), Ok(symbols(&["-p", "--types"])));
+        assert_eq!(parsed(&["-"]), Ok(symbols(&["-"])));
+        // The format is accepted and ignored
+        assert_eq!(
+            parsed(&["-s", "gnu", "-sgnu", "--format=gnu", "--format", "gnu"]),
+            Ok(symbols(&[]))
+        );
+        assert_eq!(parsed(&["--help"]), Ok(None));
+        assert_eq!(parsed(&["-h"]), Ok(None));
+        assert_eq!(parsed(&["--version"]), Ok(None));
+
+        assert_eq!(parsed(&["-x"]), Err("unknown argument '-x'".to_string()));
+        assert_eq!(parsed(&["-px"]), Err("unknown argument '-px'".to_string()));
+        assert_eq!(parsed(&["--bogus"]), Err("unknown argument '--bogus'".to_string()));
+        assert_eq!(parsed(&["--types=1"]), Err("'--types' doesn't take a value".to_string()));
+        assert_eq!(parsed(&["-s"]), Err("no value given for '-s'".to_string()));
+        assert_eq!(parsed(&["--format"]), Err("no value given for '--format'".to_string()));
+    }
+
+    #[test]
+    fn test_selected_args() {
+        let argv = strings(&["/usr/bin/cwfilt", "-p", "a__Fv"]);
+        assert_eq!(selected_args(&argv), Some(&argv[1..]));
+        let argv = strings(&["CWFILT.exe"]);
+        assert_eq!(selected_args(&argv), Some(&argv[1..]));
+        let argv = strings(&["cwdemangle", "--cxxfilt", "-t"]);
+        assert_eq!(selected_args(&argv), Some(&argv[2..]));
+        assert_eq!(selected_args(&strings(&["cwdemangle", "-t", "--cxxfilt"])), None);
+        assert_eq!(selected_args(&strings(&["cwfilter"])), None);
+        assert_eq!(selected_args(&[]), None);
+    }
+
+    #[test]
+    fn test_demangle_word() {
+        let options = DemangleOptions::default();
+        let demangle = |args: &[&str], word| {
+            let args = parse_args(&strings(args)).unwrap().unwrap();
+            demangle_word(&args, &options, word)
+        };
+        let symbol = "Think__6CActorFfR13CStateManager";
+        assert_eq!(demangle(&[], symbol).as_deref(), Some("CActor::Think(float, CStateManager&)"));
+        assert_eq!(demangle(&["-p"], symbol).as_deref(), Some("CActor::Think"));
+        assert_eq!(
+            demangle(&["-p"], "BuildLight__9CGuiLightCFv").as_deref(),
+            Some("CGuiLight::BuildLight")
+        );
+        // Variables have no parameters to strip
+        assert_eq!(demangle(&["-p"], "mInstance__6CActor").as_deref(), Some("CActor::mInstance"));
+        assert_eq!(demangle(&[], "Q23std6vector"), None);
+        assert_eq!(demangle(&["-t"], "Q23std6vector").as_deref(), Some("std::vector"));
+        assert_eq!(demangle(&["-t"], "PCc").as_deref(), Some("const char*"));
+        assert_eq!(demangle(&["-t"], "main"), None);
+        assert_eq!(demangle(&["-_"], "_a__Fv").as_deref(), Some("a()"));
+        assert_eq!(demangle(&[], "_a__Fv").as_deref(), Some("_a()"));
+    }
+}
@@ -0,0 +1,349 @@
+//! A small line editor for the REPL, with cursor movement and history.
+//!
+//! The terminal is switched to raw mode with `stty`, so no terminal library is
+//! needed. When stdin isn't a terminal, or `stty` isn't available, lines are
+//! read as they are.
+
+use std::{
+    io,
+    io::{stdin, stdout, BufRead, Read, Write},
+    process::{Command, Stdio},
+};
+
+/// Reads lines, keeping a history of those entered this session.
+pub struct Editor {
+    history: Vec<String>,
+    /// Whether the terminal is in raw mode, so that lines can be edited
+    raw: bool,
+}
+
+/// Puts the terminal in raw mode until dropped.
+pub struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    /// Enables raw mode, or returns `None` if stdin isn't a terminal.
+    pub fn enable() -> Option<RawMode> {
+        if !cfg!(unix) {
+            return None;
+        }
+        let saved = stty(&["-g"])?;
+        // Keep output processing, so that `\n` still starts a new line
+        stty(&["-icanon", "-echo", "-isig", "-ixon", "min", "1", "time", "0"])?;
+        Some(RawMode { saved: saved.trim().to_string() })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) { stty(&[&self.saved]); }
+}
+
+/// Runs `stty` on the terminal, returning its output if it succeeded.
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// What a key press does to the line being edited.
+#[derive(Debug, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    Up,
+    Down,
+    DeleteToEnd,
+    DeleteToStart,
+    DeleteWord,
+    /// Ctrl-C, which discards the line.
+    Cancel,
+    /// Ctrl-D, which ends input on an empty line.
+    EndOfInput,
+    Ignored,
+}
+
+impl Editor {
+    /// Creates an editor. Unless `raw` is set, as when a [RawMode] is held for
+    /// the session, lines are read as they are, without editing.
+    pub fn new(raw: bool) -> Self { Editor { history: Vec::new(), raw } }
+
+    /// The lines entered so far, oldest first.
+    pub fn history(&self) -> &[String] { &self.history }
+
+    /// Reads a line after showing `prompt`, returning `None` at the end of input.
+    /// Non-empty lines are added to the history.
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        let line = if self.raw {
+            self.edit_line(prompt)?
+        } else {
+            let mut line = String::new();
+            if stdin().lock().read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            Some(line.trim_end_matches(['\r', '\n']).to_string())
+        };
+        if let Some(line) = &line {
+            if !line.trim().is_empty() && self.history.last() != Some(line) {
+                self.history.push(line.clone());
+            }
+        }
+        Ok(line)
+    }
+
+    fn edit_line(&self, prompt: &str) -> io::Result<Option<String>> {
+        let stdin = stdin();
+        let mut input = stdin.lock().bytes();
+        let stdout = stdout();
+        let mut out = stdout.lock();
+        let mut line = Vec::<char>::new();
+        let mut cursor = 0;
+        // Where we are in the history, and the line being entered before moving
+        // through it
+        let mut history_index = self.history.len();
+        let mut pending = Vec::new();
+        write!(out, "{prompt}")?;
+        out.flush()?;
+        loop {
+            let key = match read_key(&mut input)? {
+                Some(key) => key,
+                None if line.is_empty() => return Ok(None),
+                None => Key::Enter,
+            };
+            match key {
+                Key::Char(c) => {
+                    line.insert(cursor, c);
+                    cursor += 1;
+                }
+                Key::Enter => {
+                    writeln!(out)?;
+                    return Ok(Some(line.into_iter().collect()));
+                }
+                Key::Backspace if cursor > 0 => {
+                    cursor -= 1;
+                    line.remove(cursor);
+                }
+                Key::Delete if cursor < line.len() => {
+                    line.remove(cursor);
+                }
+                Key::Left if cursor > 0 => cursor -= 1,
+                Key::Right if cursor < line.len() => cursor += 1,
+                Key::Home => cursor = 0,
+                Key::End => cursor = line.len(),
+                Key::Up | Key::Down => {
+                    let index = match key {
+                        Key::Up => history_index.checked_sub(1),
+                        _ => Some(history_index + 1).filter(|&i| i <= self.history.len()),
+                    };
+                    if let Some(index) = index {
+                        if history_index == self.history.len() {
+                            pending = line.clone();
+                        }
+                        history_index = index;
+                        line = match self.history.get(index) {
+                            Some(entry) => entry.chars().collect(),
+                            None => pending.clone(),
+                        };
+                        cursor = line.len();
+                    }
+                }
+                Key::DeleteToEnd => line.truncate(cursor),
+                Key::DeleteToStart => {
+                    line.drain(..cursor);
+                    cursor = 0;
+                }
+                Key::DeleteWord => {
+                    let mut start = cursor;
+                    while start > 0 && line[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    while start > 0 && !line[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    line.drain(start..cursor);
+                    cursor = start;
+                }
+                Key::Cancel => {
+                    writeln!(out, "^C")?;
+                    line.clear();
+                    cursor = 0;
+                    history_index = self.history.len();
+                }
+                Key::EndOfInput if line.is_empty() => {
+                    writeln!(out)?;
+                    return Ok(None);
+                }
+                Key::EndOfInput if cursor < line.len() => {
+                    line.remove(cursor);
+                }
+                _ => {}
+            }
+            // Redraw the line, then move back to the cursor. This counts one
+            // column per char, so the cursor lands in the wrong place after
+            // double-width characters such as kanji
+            let text = line.iter().collect::<String>();
+            write!(out, "\r{prompt}{text}\x1b[K")?;
+            if cursor < line.len() {
+                write!(out, "\x1b[{}D", line.len() - cursor)?;
+            }
+            out.flush()?;
+        }
+    }
+}
+
+/// Reads a key press, returning `None` at the end of input.
+fn read_key<R: Read>(input: &mut io::Bytes<R>) -> io::Result<Option<Key>> {
+    let byte = match input.next() {
+        Some(byte) => byte?,
+        None => return Ok(None),
+    };
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        0x7F | 0x08 => Key::Backspace,
+        0x01 => Key::Home,
+        0x02 => Key::Left,
+        0x03 => Key::Cancel,
+        0x04 => Key::EndOfInput,
+        0x05 => Key::End,
+        0x06 => Key::Right,
+        0x0B => Key::DeleteToEnd,
+        0x0E => Key::Down,
+        0x10 => Key::Up,
+        0x15 => Key::DeleteToStart,
+        0x17 => Key::DeleteWord,
+        0x1B => read_escape(input)?,
+        byte if byte < 0x20 => Key::Ignored,
+        byte => {
+            // Collect the rest of a UTF-8 sequence
+            let len = match byte {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![byte];
+            while bytes.len() < len {
+                match input.next() {
+                    Some(byte) => bytes.push(byte?),
+                    None => break,
+                }
+            }
+            match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+                Some(c) => Key::Char(c),
+                None => Key::Ignored,
+            }
+        }
+    };
+    Ok(Some(key))
+}
+
+/// Reads the rest of an escape sequence, such as `[A` for the up arrow.
+fn read_escape<R: Read>(input: &mut io::Bytes<R>) -> io::Result<Key> {
+    let mut next = || input.next().transpose();
+    let kind = match next()? {
+        Some(kind @ (b'[' | b'O')) => kind,
+        _ => return Ok(Key::Ignored),
+    };
+    // Parameters, then a final byte
+    let mut params = Vec::new();
+    let last = loop {
+        match next()? {
+            Some(byte @ 0x40..=0x7E) => break byte,
+            Some(byte) => params.push(byte),
+            None => return Ok(Key::Ignored),
+        }
+    };
+    Ok(match (kind, last, params.as_slice()) {
+        (_, b'A', _) => Key::Up,
+        (_, b'B', _) => Key::Down,
+        (_, b'C', _) => Key::Right,
+        (_, b'D', _) => Key::Left,
+        (_, b'H', _) | (b'[', b'~', b"1" | b"7") => Key::Home,
+        (_, b'F', _) | (b'[', b'~', b"4" | b"8") => Key::End,
+        (b'[', b'~', b"3") => Key::Delete,
+        _ => Key::Ignored,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(input: &[u8]) -> Vec<Key> {
+        let mut input = input.bytes();
+        let mut keys = Vec::new();
+        while let Some(key) = read_key(&mut input).unwrap() {
+            keys.push(key);
+        }
+        keys
+    }
+
+    #[test]
+    fn test_read_key() {
+        assert_eq!(keys(b"a\x01\x03\x04\x7F\x08\x17\r\n"), [
+            Key::Char('a'),
+            Key::Home,
+            Key::Cancel,
+            Key::EndOfInput,
+            Key::Backspace,
+            Key::Backspace,
+            Key::DeleteWord,
+            Key::Enter,
+            Key::Enter,
+        ]);
+        assert_eq!(keys(b"\x00\x1F"), [Key::Ignored, Key::Ignored]);
+    }
+
+    #[test]
+    fn test_read_key_utf8() {
+        assert_eq!(keys("é漢🦀".as_bytes()), [
+            Key::Char('é'),
+            Key::Char('漢'),
+            Key::Char('🦀')
+        ]);
+        // A sequence cut short by the end of input, or invalid, is ignored
+        assert_eq!(keys(b"\xE6\xBC"), [Key::Ignored]);
+        assert_eq!(keys(b"\xFF"), [Key::Ignored]);
+    }
+
+    #[test]
+    fn test_read_escape() {
+        // CSI and SS3 arrows, as sent in normal and application cursor mode
+        assert_eq!(keys(b"\x1b[A\x1b[B\x1b[C\x1b[D"), [Key::Up, Key::Down, Key::Right, Key::Left]);
+        assert_eq!(keys(b"\x1bOA\x1bOB\x1bOC\x1bOD"), [Key::Up, Key::Down, Key::Right, Key::Left]);
+        assert_eq!(keys(b"\x1b[H\x1bOF\x1b[1~\x1b[4~\x1b[7~\x1b[8~"), [
+            Key::Home,
+            Key::End,
+            Key::Home,
+            Key::End,
+            Key::Home,
+            Key::End,
+        ]);
+        assert_eq!(keys(b"\x1b[3~x"), [Key::Delete, Key::Char('x')]);
+        // Modifiers are ignored for arrows, and unknown sequences are skipped whole
+        assert_eq!(keys(b"\x1b[1;5C\x1b[15~y"), [Key::Right, Key::Ignored, Key::Char('y')]);
+    }
+
+    #[test]
+    fn test_read_escape_eof() {
+        // A lone ESC, or a sequence cut short, at the end of input
+        assert_eq!(keys(b"\x1b"), [Key::Ignored]);
+        assert_eq!(keys(b"\x1b["), [Key::Ignored]);
+        assert_eq!(keys(b"\x1b[1;"), [Key::Ignored]);
+        // ESC followed by anything else is ignored, along with that byte
+        assert_eq!(keys(b"\x1bxy"), [Key::Ignored, Key::Char('y')]);
+    }
+}
@@ -0,0 +1,381 @@
+//! Just enough of an ELF32 and `ar` reader to list symbol tables.
+
+const SHT_SYMTAB: u32 = 2;
+const STT_SECTION: u8 = 3;
+const STT_FILE: u8 = 4;
+const SHN_UNDEF: u16 = 0;
+const SHN_LORESERVE: u16 = 0xFF00;
+const SHN_ABS: u16 = 0xFFF1;
+const SHN_COMMON: u16 = 0xFFF2;
+
+/// A symbol from an ELF symbol table.
+pub struct Symbol<'a> {
+    pub name: &'a [u8],
+    pub value: u32,
+    pub size: u32,
+    /// The name of the section the symbol is defined in, or `UND`, `ABS` or
+    /// `COMMON`.
+    pub section: &'a [u8],
+    pub binding: &'static str,
+}
+
+/// Reads integers of either byte order.
+#[derive(Clone, Copy)]
+struct Reader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes<const N: usize>(self, offset: usize) -> Result<[u8; N], String> {
+        offset
+            .checked_add(N)
+            .and_then(|end| self.data.get(offset..end))
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| format!("Truncated ELF file reading offset {offset:#x}"))
+    }
+
+    fn u16(self, offset: usize) -> Result<u16, String> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(self, offset: usize) -> Result<u32, String> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    /// Returns `len` bytes at `offset`.
+    fn slice(self, offset: u32, len: u32) -> Result<&'a [u8], String> {
+        let (offset, len) = (offset as usize, len as usize);
+        offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| format!("Truncated ELF file reading {len:#x} bytes at {offset:#x}"))
+    }
+}
+
+/// A section header.
+struct Section {
+    name: u32,
+    kind: u32,
+    offset: u32,
+    size: u32,
+    link: u32,
+    entry_size: u32,
+}
+
+pub fn is_elf(data: &[u8]) -> bool { data.starts_with(b"\x7fELF") }
+
+/// Reads the symbols of a 32-bit ELF file of either byte order, in symbol table
+/// order. Section and file symbols are skipped.
+pub fn read_symbols(data: &[u8]) -> Result<Vec<Symbol<'_>>, String> {
+    if !is_elf(data) {
+        return Err("Not an ELF file".to_string());
+    }
+    if data.get(4) != Some(&1) {
+        return Err("Only 32-bit ELF files are supported".to_string());
+    }
+    let big_endian = match data.get(5) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => return Err("Unknown ELF byte order".to_string()),
+    };
+    let r = Reader { data, big_endian };
+    let section_offset = r.u32(0x20)? as usize;
+    let section_size = r.u16(0x2E)? as usize;
+    let section_count = r.u16(0x30)? as usize;
+    let names_index = r.u16(0x32)? as usize;
+    if section_size < 40 {
+        return Err(format!("Invalid ELF section header size {section_size}"));
+    }
+    let sections = (0..section_count)
+        .map(|i| {
+            let offset = section_offset + i * section_size;
+            Ok(Section {
+                name: r.u32(offset)?,
+                kind: r.u32(offset + 4)?,
+                offset: r.u32(offset + 16)?,
+                size: r.u32(offset + 20)?,
+                link: r.u32(offset + 24)?,
+                entry_size: r.u32(offset + 36)?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let section_data = |index: usize| -> Result<&[u8], String> {
+        let section =
+            sections.get(index).ok_or_else(|| format!("Invalid section index {index}"))?;
+        r.slice(section.offset, section.size)
+    };
+    let section_names = section_data(names_index)?;
+
+    let mut symbols = Vec::new();
+    for symtab in sections.iter().filter(|s| s.kind == SHT_SYMTAB) {
+        let entry_size = if symtab.entry_size == 0 { 16 } else { symtab.entry_size as usize };
+        if entry_size < 16 {
+            return Err(format!("Invalid ELF symbol size {entry_size}"));
+        }
+        let names = section_data(symtab.link as usize)?;
+        let table = Reader { data: r.slice(symtab.offset, symtab.size)?, big_endian };
+        // The first symbol is always null
+        for offset in
+            (entry_size..table.data.len() - table.data.len() % entry_size).step_by(entry_size)
+        {
+            let info = table.data[offset + 12];
+            if matches!(info & 0xF, STT_SECTION | STT_FILE) {
+                continue;
+            }
+            let name = c_str(names, table.u32(offset)?)?;
+            if name.is_empty() {
+                continue;
+            }
+            let section: &[u8] = match table.u16(offset + 14)? {
+                SHN_UNDEF => b"UND",
+                SHN_ABS => b"ABS",
+                SHN_COMMON => b"COMMON",
+                index if index >= SHN_LORESERVE => b"?",
+                index => match sections.get(index as usize) {
+                    Some(section) => c_str(section_names, section.name)?,
+                    None => b"?",
+                },
+            };
+            let binding = match info >> 4 {
+                0 => "local",
+                1 => "global",
+                2 => "weak",
+                _ => "?",
+            };
+            symbols.push(Symbol {
+                name,
+                value: table.u32(offset + 4)?,
+                size: table.u32(offset + 8)?,
+                section,
+                binding,
+            });
+        }
+    }
+    Ok(symbols)
+}
+
+/// Reads the NUL-terminated string at `offset` in a string table.
+fn c_str(table: &[u8], offset: u32) -> Result<&[u8], String> {
+    let str = table
+        .get(offset as usize..)
+        .ok_or_else(|| format!("Invalid ELF string table offset {offset:#x}"))?;
+    Ok(str.split(|&c| c == 0).next().unwrap_or(str))
+}
+
+pub fn is_archive(data: &[u8]) -> bool { data.starts_with(b"!<arch>\n") }
+
+/// Reads the members of an `ar` archive, skipping its symbol index. Long names
+/// are supported in both the GNU and BSD styles.
+pub fn read_archive(data: &[u8]) -> Result<Vec<(String, &[u8])>, String> {
+    if !is_archive(data) {
+        return Err("Not an archive".to_string());
+    }
+    let mut members = Vec::new();
+    let mut long_names: &[u8] = &[];
+    let mut offset = 8;
+    while offset < data.len() {
+        let header = data
+            .get(offset..offset + 60)
+            .ok_or_else(|| format!("Truncated archive member header at {offset:#x}"))?;
+        if &header[58..60] != b"`\n" {
+            return Err(format!("Invalid archive member header at {offset:#x}"));
+        }
+        let name = trim_ascii(&header[..16]);
+        let size = std::str::from_utf8(trim_ascii(&header[48..58]))
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .ok_or_else(|| format!("Invalid archive member size at {offset:#x}"))?;
+        let start = offset + 60;
+        let mut member = start
+            .checked_add(size)
+            .and_then(|end| data.get(start..end))
+            .ok_or_else(|| format!("Truncated archive member at {offset:#x}"))?;
+        let header_offset = offset;
+        // Members are aligned to 2 bytes
+        offset = start + size + (size & 1);
+
+        let name = if name == b"/" || name == b"/SYM64/" || name.starts_with(b"__.SYMDEF") {
+            continue;
+        } else if name == b"//" {
+            long_names = member;
+            continue;
+        } else if let Some(len) = name.strip_prefix(b"#1/") {
+            // BSD: the name follows the header
+            let len = std::str::from_utf8(len)
+                .ok()
+                .and_then(|s| s.parse::<usize>().ok())
+                .filter(|&len| len <= member.len())
+                .ok_or_else(|| format!("Invalid archive member name at {header_offset:#x}"))?;
+            let (name, rest) = member.split_at(len);
+            member = rest;
+            name.split(|&c| c == 0).next().unwrap_or(name)
+        } else if let Some(index) = name.strip_prefix(b"/") {
+            // GNU: an offset into the long name table
+            let index = std::str::from_utf8(index)
+                .ok()
+                .and_then(|s| s.parse::<usize>().ok())
+                .and_then(|i| long_names.get(i..))
+                .ok_or_else(|| format!("Invalid archive member name at {header_offset:#x}"))?;
+            let end = index.iter().position(|&c| c == b'\n').unwrap_or(index.len());
+            let name = &index[..end];
+            name.strip_suffix(b"/").unwrap_or(name)
+        } else {
+            name.strip_suffix(b"/").unwrap_or(name)
+        };
+        members.push((String::from_utf8_lossy(name).into_owned(), member));
+    }
+    Ok(members)
+}
+
+fn trim_ascii(str: &[u8]) -> &[u8] {
+    let start = str.iter().position(|c| !c.is_ascii_whitespace()).unwrap_or(str.len());
+    let end = str.iter().rposition(|c| !c.is_ascii_whitespace()).map_or(start, |i| i + 1);
+    &str[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a relocatable object with a defined, an undefined, a file and a
+    /// section symbol.
+    fn object(big_endian: bool) -> Vec<u8> {
+        let u16 = |v: u16| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+        let u32 = |v: u32| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+        let text = [0u8; 8];
+        let strtab = b"\0Update__6CActorFv\0OSReport\0CActor.cpp\0";
+        let shstrtab = b"\0.text\0.symtab\0.strtab\0.shstrtab\0";
+        let mut symtab = vec![0u8; 16];
+        for (name, value, size, info, section) in [
+            (28, 0, 0, STT_FILE, SHN_ABS),
+            (0, 0, 0, STT_SECTION, 1),
+            (1, 0x10, 8, 0x12, 1),
+            (19, 0, 0, 0x10, SHN_UNDEF),
+            (1, 0x20, 0, 0x20, SHN_COMMON),
+        ] {
+            symtab.extend(u32(name));
+            symtab.extend(u32(value));
+            symtab.extend(u32(size));
+            symtab.extend([info, 0]);
+            symtab.extend(u16(section));
+        }
+
+        let mut data = vec![0u8; 0x34];
+        let mut sections = vec![[0u32; 10]];
+        for (name, kind, contents, link, entry_size) in [
+            (1, 1, &text[..], 0, 0),
+            (7, SHT_SYMTAB, &symtab[..], 3, 16),
+            (15, 3, &strtab[..], 0, 0),
+            (23, 3, &shstrtab[..], 0, 0),
+        ] {
+            let offset = data.len() as u32;
+            data.extend(contents);
+            let size = contents.len() as u32;
+            sections.push([name, kind, 0, 0, offset, size, link, 0, 0, entry_size]);
+        }
+        let section_offset = data.len() as u32;
+        for section in &sections {
+            section.iter().for_each(|&v| data.extend(u32(v)));
+        }
+        data[..6].copy_from_slice(&[0x7F, b'E', b'L', b'F', 1, if big_endian { 2 } else { 1 }]);
+        data[0x20..0x24].copy_from_slice(&u32(section_offset));
+        data[0x2E..0x30].copy_from_slice(&u16(40));
+        data[0x30..0x32].copy_from_slice(&u16(sections.len() as u16));
+        data[0x32..0x34].copy_from_slice(&u16(4));
+        data
+    }
+
+    /// Formats each symbol as `name value size section binding`.
+    fn symbols(data: &[u8]) -> Result<Vec<String>, String> {
+        Ok(read_symbols(data)?
+            .into_iter()
+            .map(|s| {
+                let name = String::from_utf8_lossy(s.name);
+                let section = String::from_utf8_lossy(s.section);
+                format!("{name} {:#x} {} {section} {}", s.value, s.size, s.binding)
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_read_symbols() {
+        for big_endian in [true, false] {
+            assert_eq!(
+                symbols(&object(big_endian)),
+                Ok(vec![
+                    "Update__6CActorFv 0x10 8 .text global".to_string(),
+                    "OSReport 0x0 0 UND global".to_string(),
+                    "Update__6CActorFv 0x20 0 COMMON weak".to_string(),
+                ])
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_symbols_invalid() {
+        let data = object(true);
+        assert_eq!(symbols(b"\x7fEL").unwrap_err(), "Not an ELF file");
+        assert_eq!(symbols(&data[..0x28]).unwrap_err(), "Truncated ELF file reading offset 0x2e");
+        assert_eq!(
+            symbols(&data[..data.len() - 1]).unwrap_err(),
+            format!("Truncated ELF file reading offset {:#x}", data.len() - 4)
+        );
+        let mut bad = data.clone();
+        bad[4] = 2;
+        assert_eq!(symbols(&bad).unwrap_err(), "Only 32-bit ELF files are supported");
+        let mut bad = data.clone();
+        bad[5] = 0;
+        assert_eq!(symbols(&bad).unwrap_err(), "Unknown ELF byte order");
+        let mut bad = data;
+        bad[0x2F] = 16;
+        assert_eq!(symbols(&bad).unwrap_err(), "Invalid ELF section header size 16");
+    }
+
+    fn member(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut out = format!("{name:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n", 0, 0, 0, 644, data.len())
+            .into_bytes();
+        out.extend(data);
+        if data.len() % 2 == 1 {
+            out.push(b'\n');
+        }
+        out
+    }
+
+    #[test]
+    fn test_read_archive() {
+        let long_name = "CGameAreaManager_very_long.o";
+        let mut data = b"!<arch>\n".to_vec();
+        data.extend(member("/", b"\0\0\0\0"));
+        data.extend(member("//", format!("{long_name}/\nother.o/\n").as_bytes()));
+        data.extend(member("/0", b"gnu"));
+        data.extend(member("short.o/", b"short"));
+        data.extend(member(
+            &format!("#1/{}", long_name.len()),
+            format!("{long_name}bsd").as_bytes(),
+        ));
+        data.extend(member("/30", b"other"));
+        let members = read_archive(&data).unwrap();
+        assert_eq!(members, vec![
+            (long_name.to_string(), b"gnu".as_slice()),
+            ("short.o".to_string(), b"short".as_slice()),
+            (long_name.to_string(), b"bsd".as_slice()),
+            ("other.o".to_string(), b"other".as_slice()),
+        ]);
+
+        assert_eq!(read_archive(b"!<arch>\n"), Ok(vec![]));
+        assert_eq!(read_archive(b"garbage").unwrap_err(), "Not an archive");
+        assert_eq!(
+            read_archive(&data[..20]).unwrap_err(),
+            "Truncated archive member header at 0x8"
+        );
+        let mut truncated = b"!<arch>\n".to_vec();
+        truncated.extend(&member("a.o/", b"data")[..62]);
+        assert_eq!(read_archive(&truncated).unwrap_err(), "Truncated archive member at 0x8");
+        let mut bad = b"!<arch>\n".to_vec();
+        bad.extend(member("/99", b"data"));
+        assert_eq!(read_archive(&bad).unwrap_err(), "Invalid archive member name at 0x8");
+    }
+}
@@ -0,0 +1,299 @@
+//! Machine-readable output for `--format`.
+
+use std::{io, io::Write, str::FromStr};
+
+use cwdemangle::Parts;
+
+/// How demangled symbols are written.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One demangled name per line.
+    Text,
+    /// A JSON array of records.
+    Json,
+    /// One JSON record per line.
+    Jsonl,
+    /// Tab-separated records, with a header line.
+    Tsv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::Jsonl),
+            "tsv" => Ok(Format::Tsv),
+            _ => Err(format!("unknown format `{s}`, expected text, json, jsonl or tsv")),
+        }
+    }
+}
+
+const TSV_COLUMNS: &[&str] = &[
+    "mangled",
+    "success",
+    "demangled",
+    "kind",
+    "class_path",
+    "name",
+    "parameters",
+    "return_type",
+    "const",
+    "local_function",
+    "local_variable",
+    "local_index",
+    "guard",
+];
+
+/// The result of demangling one symbol.
+pub struct Record<'a> {
+    pub mangled: &'a [u8],
+    /// The demangled name, or `None` if the symbol failed to demangle.
+    pub demangled: Option<&'a str>,
+    pub parts: Option<&'a Parts>,
+}
+
+impl Format {
+    /// Writes anything that comes before the first record.
+    pub fn begin<W: Write>(self, out: &mut W) -> io::Result<()> {
+        match self {
+            Format::Json => write!(out, "["),
+            Format::Tsv => writeln!(out, "{}", TSV_COLUMNS.join("\t")),
+            Format::Text | Format::Jsonl => Ok(()),
+        }
+    }
+
+    /// Writes anything that comes after the last of `count` records.
+    pub fn end<W: Write>(self, out: &mut W, count: usize) -> io::Result<()> {
+        match self {
+            Format::Json if count == 0 => writeln!(out, "]"),
+            Format::Json => writeln!(out, "\n]"),
+            Format::Text | Format::Jsonl | Format::Tsv => Ok(()),
+        }
+    }
+
+    /// Writes a record, given the number of records written before it.
+    pub fn record<W: Write>(self, out: &mut W, index: usize, record: &Record) -> io::Result<()> {
+        match self {
+            Format::Text => match record.demangled {
+                Some(demangled) => writeln!(out, "{demangled}"),
+                None => out.write_all(record.mangled).and_then(|_| writeln!(out)),
+            },
+            Format::Json => {
+                write!(out, "{}\n  ", if index == 0 { "" } else { "," })?;
+                write_json_record(out, record)
+            }
+            Format::Jsonl => {
+                write_json_record(out, record)?;
+                writeln!(out)
+            }
+            Format::Tsv => write_tsv_record(out, record),
+        }
+    }
+}
+
+fn write_json_record<W: Write>(out: &mut W, record: &Record) -> io::Result<()> {
+    write!(out, "{{\"mangled\":")?;
+    write_json_str(out, &String::from_utf8_lossy(record.mangled))?;
+    write!(out, ",\"success\":{},\"demangled\":", record.demangled.is_some())?;
+    write_json_opt(out, record.demangled)?;
+    if let Some(parts) = record.parts {
+        write!(out, ",\"kind\":\"{}\",\"class_path\":", parts.kind.as_str())?;
+        write_json_array(out, &parts.class_path)?;
+        write!(out, ",\"name\":")?;
+        write_json_str(out, &parts.name)?;
+        write!(out, ",\"parameters\":")?;
+        match &parts.parameters {
+            Some(parameters) => write_json_array(out, parameters)?,
+            None => write!(out, "null")?,
+        }
+        write!(out, ",\"return_type\":")?;
+        write_json_opt(out, parts.return_type.as_deref())?;
+        write!(out, ",\"const\":{},\"local_static\":", parts.is_const)?;
+        match &parts.local_static {
+            Some(local) => {
+                write!(out, "{{\"function\":")?;
+                write_json_str(out, &local.function)?;
+                write!(out, ",\"variable\":")?;
+                write_json_opt(out, local.variable.as_deref())?;
+                match local.index {
+                    Some(index) => write!(out, ",\"index\":{index}")?,
+                    None => write!(out, ",\"index\":null")?,
+                }
+                write!(out, ",\"guard\":{}}}", local.is_guard)?;
+            }
+            None => write!(out, "null")?,
+        }
+    }
+    write!(out, "}}")
+}
+
+fn write_json_array<W: Write>(out: &mut W, strs: &[String]) -> io::Result<()> {
+    write!(out, "[")?;
+    for (i, str) in strs.iter().enumerate() {
+        if i > 0 {
+            write!(out, ",")?;
+        }
+        write_json_str(out, str)?;
+    }
+    write!(out, "]")
+}
+
+pub fn write_json_opt<W: Write>(out: &mut W, str: Option<&str>) -> io::Result<()> {
+    match str {
+        Some(str) => write_json_str(out, str),
+        None => write!(out, "null"),
+    }
+}
+
+pub fn write_json_str<W: Write>(out: &mut W, str: &str) -> io::Result<()> {
+    write!(out, "\"")?;
+    let mut start = 0;
+    for (i, c) in str.char_indices() {
+        let escaped = match c {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            c if c.is_control() => "",
+            _ => continue,
+        };
+        write!(out, "{}", &str[start..i])?;
+        if escaped.is_empty() {
+            write!(out, "\\u{:04x}", c as u32)?;
+        } else {
+            write!(out, "{escaped}")?;
+        }
+        start = i + c.len_utf8();
+    }
+    write!(out, "{}\"", &str[start..])
+}
+
+fn write_tsv_record<W: Write>(out: &mut W, record: &Record) -> io::Result<()> {
+    let mut fields = vec![
+        String::from_utf8_lossy(record.mangled).into_owned(),
+        record.demangled.is_some().to_string(),
+        record.demangled.unwrap_or_default().to_string(),
+    ];
+    if let Some(parts) = record.parts {
+        let local = parts.local_static.as_ref();
+        fields.extend([
+            parts.kind.as_str().to_string(),
+            parts.class_path.join("::"),
+            parts.name.clone(),
+            // Parenthesized so that functions without parameters stand out
+            parts.parameters.as_ref().map(|p| format!("({})", p.join(", "))).unwrap_or_default(),
+            parts.return_type.clone().unwrap_or_default(),
+            parts.is_const.to_string(),
+            local.map(|l| l.function.clone()).unwrap_or_default(),
+            local.and_then(|l| l.variable.clone()).unwrap_or_default(),
+            local.and_then(|l| l.index).map(|i| i.to_string()).unwrap_or_default(),
+            local.map(|l| l.is_guard.to_string()).unwrap_or_default(),
+        ]);
+    } else {
+        fields.resize(TSV_COLUMNS.len(), String::new());
+    }
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            write!(out, "\t")?;
+        }
+        write_tsv_field(out, field)?;
+    }
+    writeln!(out)
+}
+
+/// Writes a field, escaping the characters that would break up the record.
+fn write_tsv_field<W: Write>(out: &mut W, field: &str) -> io::Result<()> {
+    let mut start = 0;
+    for (i, c) in field.char_indices() {
+        let escaped = match c {
+            '\\' => "\\\\",
+            '\t' => "\\t",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            _ => continue,
+        };
+        write!(out, "{}{escaped}", &field[start..i])?;
+        start = i + 1;
+    }
+    write!(out, "{}", &field[start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use cwdemangle::{demangle_parts, DemangleOptions};
+
+    use super::*;
+
+    fn written(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn record(format: Format, mangled: &str) -> String {
+        let parts = demangle_parts(mangled, &DemangleOptions::default());
+        let record = Record {
+            mangled: mangled.as_bytes(),
+            demangled: parts.as_ref().map(|p| p.demangled.as_str()),
+            parts: parts.as_ref(),
+        };
+        written(|out| format.record(out, 0, &record))
+    }
+
+    #[test]
+    fn test_json_str() {
+        let json = |str| written(|out| write_json_str(out, str));
+        assert_eq!(json(""), r#""""#);
+        assert_eq!(json(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(json(r"C:\dir\"), r#""C:\\dir\\""#);
+        assert_eq!(json("a\tb\r\nc"), r#""a\tb\r\nc""#);
+        assert_eq!(json("\0\x1b\u{7f}é"), r#""\u0000\u001b\u007fé""#);
+        assert_eq!(written(|out| write_json_opt(out, None)), "null");
+    }
+
+    #[test]
+    fn test_tsv_field() {
+        let tsv = |str| written(|out| write_tsv_field(out, str));
+        assert_eq!(tsv("a\tb"), r"a\tb");
+        assert_eq!(tsv("a\r\nb"), r"a\r\nb");
+        assert_eq!(tsv(r"a\tb"), r"a\\tb");
+        assert_eq!(tsv("é\t"), r"é\t");
+    }
+
+    #[test]
+    fn test_failed_record() {
+        assert_eq!(
+            record(Format::Jsonl, "bad\t\"name\""),
+            "{\"mangled\":\"bad\\t\\\"name\\\"\",\"success\":false,\"demangled\":null}\n"
+        );
+        assert_eq!(
+            record(Format::Tsv, "bad\tname"),
+            format!("bad\\tname\tfalse{}\n", "\t".repeat(TSV_COLUMNS.len() - 2))
+        );
+        assert_eq!(record(Format::Text, "bad"), "bad\n");
+    }
+
+    #[test]
+    fn test_record() {
+        let symbol = "Think__6CActorFfR13CStateManager";
+        assert_eq!(
+            record(Format::Jsonl, symbol),
+            concat!(
+                r#"{"mangled":"Think__6CActorFfR13CStateManager","success":true,"#,
+                r#""demangled":"CActor::Think(float, CStateManager&)","kind":"function","#,
+                r#""class_path":["CActor"],"name":"Think","parameters":["float","CStateManager&"],"#,
+                r#""return_type":null,"const":false,"local_static":null}"#,
+                "\n"
+            )
+        );
+        assert_eq!(
+            record(Format::Tsv, symbol),
+            "Think__6CActorFfR13CStateManager\ttrue\tCActor::Think(float, CStateManager&)\t\
+             function\tCActor\tThink\t(float, CStateManager&)\t\tfalse\t\t\t\t\n"
+        );
+    }
+}
@@ -1,33 +1,362 @@
+use std::{
+    fs,
+    fs::File,
+    io,
+    io::{stdin, stdout, BufRead, BufReader, BufWriter, Read, Write},
+    iter::once,
+};
+
 use argh::FromArgs;
-use cwdemangle::{demangle, DemangleOptions};
+use cwdemangle::{
+    demangle, demangle_affixed, demangle_bytes, demangle_parts, demangle_tokens, DemangleOptions,
+    Encoding, Parts, DEFAULT_AFFIXES,
+};
 
-use crate::argh_cargo::from_env;
+use crate::{
+    argh_cargo::{from_env, STDIN},
+    cmd::Command,
+    color::ColorChoice,
+    format::{Format, Record},
+};
 
 mod argh_cargo;
+mod cmd;
+mod color;
+mod cxxfilt;
+mod editor;
+mod elf;
+mod format;
+mod pattern;
+mod symbols;
 
 #[derive(FromArgs)]
 /// A CodeWarrior C++ symbol demangler.
+///
+/// Symbols are read from stdin, one per line, if none are given.
 struct Args {
-    /// the symbol to demangle
+    /// the symbols to demangle, or `-` to read them from stdin
     #[argh(positional)]
-    symbol: String,
+    symbols: Vec<String>,
+    /// read symbols from a file, one per line, or `-` for stdin (may be repeated)
+    #[argh(option)]
+    input: Vec<String>,
+    /// replace mangled names found anywhere in the input text, like c++filt,
+    /// passing everything else through unchanged
+    #[argh(switch)]
+    filter: bool,
+    /// output format: text (default), json, jsonl or tsv. Structured formats
+    /// record failures instead of stopping
+    #[argh(option, default = "Format::Text")]
+    format: Format,
+    /// when to colour demangled names: auto (default, when stdout is a
+    /// terminal), always or never
+    #[argh(option, default = "ColorChoice::Auto")]
+    color: ColorChoice,
+    /// print symbols that fail to demangle unchanged and continue
+    #[argh(switch)]
+    keep_going: bool,
+    /// with --keep-going, exit with an error if any symbol failed to demangle
+    #[argh(switch)]
+    strict: bool,
     /// disable replacing `(void)` with `()`
     #[argh(switch)]
     keep_void: bool,
     /// enable Metrowerks extensions
     #[argh(switch)]
     mw_extensions: bool,
+    /// strip tool-added prefixes and suffixes (`fn_`, `.1234`, etc.) before demangling
+    #[argh(switch)]
+    strip_affixes: bool,
+    /// act like llvm-cxxfilt, taking its options (-n, -p, -t, -_) instead. Must
+    /// come first; running the binary as `cwfilt` does the same
+    #[argh(switch)]
+    cxxfilt: bool,
+    #[argh(subcommand)]
+    command: Option<Command>,
+}
+
+/// Demangles symbols in order, writing one line per symbol.
+struct Batch<'a, W: Write> {
+    args: &'a Args,
+    options: DemangleOptions,
+    /// Whether to colour demangled names.
+    color: bool,
+    out: W,
+    total: usize,
+    failed: usize,
+}
+
+impl<W: Write> Batch<'_, W> {
+    /// Breaks the symbol down into its parts for structured formats.
+    fn parts(&self, symbol: &[u8]) -> Option<Parts> {
+        if self.args.format == Format::Text {
+            return None;
+        }
+        let symbol = std::str::from_utf8(symbol).ok()?;
+        if self.args.strip_affixes {
+            let affixed = demangle_affixed(symbol, DEFAULT_AFFIXES, &self.options)?;
+            demangle_parts(affixed.mangled, &self.options)
+        } else {
+            demangle_parts(symbol, &self.options)
+        }
+    }
+
+    fn symbol(&mut self, symbol: &[u8]) -> Result<(), String> {
+        if self.args.filter {
+            return self.filter(symbol).and_then(|_| writeln!(self.out)).map_err(write_error);
+        }
+        let demangled = demangle_symbol(self.args, &self.options, self.color, symbol);
+        if demangled.is_none() {
+            if !self.args.keep_going && self.args.format == Format::Text {
+                self.out.flush().map_err(write_error)?;
+                return Err("Failed to demangle symbol".to_string());
+            }
+            self.failed += 1;
+        }
+        let parts = self.parts(symbol);
+        let record =
+            Record { mangled: symbol, demangled: demangled.as_deref(), parts: parts.as_ref() };
+        self.args.format.record(&mut self.out, self.total, &record).map_err(write_error)?;
+        self.total += 1;
+        Ok(())
+    }
+
+    /// Writes `text` with each mangled name in it replaced by its demangled form.
+    fn filter(&mut self, text: &[u8]) -> io::Result<()> {
+        let (args, options, color) = (self.args, &self.options, self.color);
+        filter(&mut self.out, text, &|symbol| demangle_symbol(args, options, color, symbol))
+    }
+
+    /// Demangles each non-empty line of `reader`.
+    fn lines<R: BufRead>(&mut self, reader: R, name: &str) -> Result<(), String> {
+        if self.args.filter {
+            return self.filter_lines(reader, name);
+        }
+        for line in reader.split(b'\n') {
+            let line = line.map_err(|e| format!("Failed to read {name}: {e}"))?;
+            let line = line.strip_suffix(b"\r").unwrap_or(&line);
+            if !line.is_empty() {
+                self.symbol(line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies `reader` to the output a line at a time, replacing mangled names.
+    fn filter_lines<R: BufRead>(&mut self, mut reader: R, name: &str) -> Result<(), String> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => return Ok(()),
+                Ok(_) => {}
+                Err(e) => return Err(format!("Failed to read {name}: {e}")),
+            }
+            // Flush each line so that output from long-running commands isn't held up
+            self.filter(&line).and_then(|_| self.out.flush()).map_err(write_error)?;
+        }
+    }
+}
+
+/// Demangles a symbol as the batch options say, which may colour it.
+fn demangle_symbol(
+    args: &Args,
+    options: &DemangleOptions,
+    color: bool,
+    symbol: &[u8],
+) -> Option<String> {
+    let symbol = match std::str::from_utf8(symbol) {
+        Ok(symbol) => symbol,
+        // Identifiers may be in another encoding; show those bytes escaped
+        Err(_) => return demangle_bytes(symbol, Encoding::Utf8, options),
+    };
+    if color {
+        let (prefix, mangled, suffix) = if args.strip_affixes {
+            let affixed = demangle_affixed(symbol, DEFAULT_AFFIXES, options)?;
+            (affixed.prefix, affixed.mangled, affixed.suffix)
+        } else {
+            ("", symbol, "")
+        };
+        let tokens = demangle_tokens(mangled, options)?;
+        return Some(format!("{prefix}{}{suffix}", color::paint(&tokens)));
+    }
+    if args.strip_affixes {
+        demangle_affixed(symbol, DEFAULT_AFFIXES, options).map(|a| a.display())
+    } else {
+        demangle(symbol, options)
+    }
+}
+
+/// Writes `text` with each mangled name in it replaced by its demangled form.
+fn filter<W: Write>(
+    out: &mut W,
+    mut text: &[u8],
+    demangle: &dyn Fn(&[u8]) -> Option<String>,
+) -> io::Result<()> {
+    while let Some(&first) = text.first() {
+        let len = text
+            .iter()
+            .position(|&c| is_symbol_char(c) != is_symbol_char(first))
+            .unwrap_or(text.len());
+        let (chunk, rest) = text.split_at(len);
+        if is_symbol_char(first) {
+            filter_token(out, chunk, demangle)?;
+        } else {
+            out.write_all(chunk)?;
+        }
+        text = rest;
+    }
+    Ok(())
+}
+
+/// Writes `token`, demangling the longest runs of it that are mangled names.
+/// Runs end at a comma, so that lists like `a__Fv,b__Fv` are handled while
+/// names with several template arguments are kept whole.
+fn filter_token<W: Write>(
+    out: &mut W,
+    mut token: &[u8],
+    demangle: &dyn Fn(&[u8]) -> Option<String>,
+) -> io::Result<()> {
+    loop {
+        if let Some(rest) = token.strip_prefix(b",") {
+            out.write_all(b",")?;
+            token = rest;
+            continue;
+        }
+        if token.is_empty() {
+            return Ok(());
+        }
+        let ends: Vec<usize> = token
+            .iter()
+            .enumerate()
+            .filter(|&(_, &c)| c == b',')
+            .map(|(i, _)| i)
+            .chain(once(token.len()))
+            .collect();
+        let found = ends.iter().rev().find_map(|&end| demangle(&token[..end]).map(|d| (end, d)));
+        let end = match found {
+            Some((end, demangled)) => {
+                out.write_all(demangled.as_bytes())?;
+                end
+            }
+            None => {
+                out.write_all(&token[..ends[0]])?;
+                ends[0]
+            }
+        };
+        token = &token[end..];
+    }
+}
+
+/// Whether `c` can be part of a mangled name found by `--filter`. Bytes outside
+/// of ASCII are included for identifiers in other encodings.
+fn is_symbol_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric()
+        || matches!(c, b'_' | b'<' | b'>' | b',' | b'@' | b'$')
+        || !c.is_ascii()
+}
+
+fn write_error(e: io::Error) -> String { format!("Failed to write output: {e}") }
+
+/// Reads the whole of a file, or stdin for `-`.
+fn read_input(path: &str) -> Result<Vec<u8>, String> {
+    if path == STDIN {
+        let mut data = Vec::new();
+        stdin().lock().read_to_end(&mut data).map_err(|e| format!("Failed to read stdin: {e}"))?;
+        Ok(data)
+    } else {
+        fs::read(path).map_err(|e| format!("Failed to read {path}: {e}"))
+    }
 }
 
-fn main() -> Result<(), &'static str> {
+fn main() -> Result<(), String> {
+    let argv = std::env::args().collect::<Vec<_>>();
+    if let Some(args) = cxxfilt::selected_args(&argv) {
+        return cxxfilt::run(args);
+    }
     let args: Args = from_env();
-    return if let Some(symbol) = demangle(args.symbol.as_str(), &DemangleOptions {
+    if args.cxxfilt {
+        return Err("--cxxfilt must be the first argument".to_string());
+    }
+    let options = DemangleOptions {
         omit_empty_parameters: !args.keep_void,
         mw_extensions: args.mw_extensions,
-    }) {
-        println!("{symbol}");
-        Ok(())
-    } else {
-        Err("Failed to demangle symbol")
     };
+    if let Some(command) = &args.command {
+        return command.run(&options);
+    }
+    let (stdin, stdout) = (stdin(), stdout());
+    let out = BufWriter::new(stdout.lock());
+    if args.filter && args.format != Format::Text {
+        return Err("--format can't be used with --filter".to_string());
+    }
+    // Structured formats are for other programs, so they're never coloured
+    let color = args.color.enabled() && args.format == Format::Text;
+    let mut batch = Batch { args: &args, options, color, out, total: 0, failed: 0 };
+    args.format.begin(&mut batch.out).map_err(write_error)?;
+    if args.symbols.is_empty() && args.input.is_empty() {
+        batch.lines(stdin.lock(), "stdin")?;
+    }
+    for symbol in &args.symbols {
+        if symbol == STDIN {
+            batch.lines(stdin.lock(), "stdin")?;
+        } else {
+            batch.symbol(symbol.as_bytes())?;
+        }
+    }
+    for path in &args.input {
+        if path == STDIN {
+            batch.lines(stdin.lock(), "stdin")?;
+            continue;
+        }
+        let file = File::open(path).map_err(|e| format!("Failed to open {path}: {e}"))?;
+        batch.lines(BufReader::new(file), path)?;
+    }
+    args.format.end(&mut batch.out, batch.total).map_err(write_error)?;
+    batch.out.flush().map_err(write_error)?;
+    if batch.failed > 0 {
+        eprintln!("{} of {} symbols failed to demangle", batch.failed, batch.total);
+        if args.strict {
+            return Err("Some symbols failed to demangle".to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filtered(text: &[u8]) -> Vec<u8> {
+        let options = DemangleOptions::default();
+        let demangle = |symbol: &[u8]| match std::str::from_utf8(symbol) {
+            Ok(symbol) => demangle(symbol, &options),
+            Err(_) => demangle_bytes(symbol, Encoding::Utf8, &options),
+        };
+        let mut out = Vec::new();
+        filter(&mut out, text, &demangle).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_filter() {
+        assert_eq!(filtered(b"a__Fv,b__Fv\n"), b"a(),b()\n");
+        // Not a mangled name, in whole or split at the comma
+        assert_eq!(filtered(b"foo<int,int>__Fv"), b"foo<int,int>__Fv");
+        assert_eq!(filtered(b"foo<i,i>__Fv,x"), b"foo<int, int>(),x");
+        assert_eq!(
+            filtered(b"Called Update__6CActorFv. (See __dt__6CActorFv!) Then, main."),
+            b"Called CActor::Update(). (See CActor::~CActor()!) Then, main.".as_slice()
+        );
+        assert_eq!(
+            filtered(b"  bl Think__6CActorFfR13CStateManager\r\n"),
+            b"  bl CActor::Think(float, CStateManager&)\r\n"
+        );
+        // Names in another encoding are escaped, and other bytes are copied
+        assert_eq!(
+            filtered(b"\x82\xa0 call __dt__2\x82\xa0Fv\n"),
+            b"\x82\xa0 call \\x82\\xa0::~\\x82\\xa0()\n".as_slice()
+        );
+        assert_eq!(filtered(b""), b"");
+    }
 }
@@ -0,0 +1,543 @@
+//! Glob and regular expression matching for `search`.
+//!
+//! Patterns are compiled to a few instructions and run as an NFA, following
+//! every alternative at once, so matching takes linear time in the length of
+//! the text however the pattern is written.
+
+/// Nested groups allowed in a regular expression.
+const MAX_DEPTH: usize = 64;
+/// Instructions allowed in a compiled pattern, which counted repetitions
+/// such as `a{1000}` multiply.
+const MAX_INSTRUCTIONS: usize = 100_000;
+
+/// A set of characters, such as `[a-z_]`.
+#[derive(Clone)]
+struct Class {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl Class {
+    fn new(ranges: &[(char, char)], negated: bool) -> Self {
+        Class { ranges: ranges.to_vec(), negated }
+    }
+
+    fn matches(&self, c: char) -> bool {
+        self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != self.negated
+    }
+}
+
+enum Node {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat { node: Box<Node>, min: u32, max: Option<u32> },
+}
+
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    /// Matches only at the start of the text.
+    Start,
+    /// Matches only at the end of the text.
+    End,
+    /// Continues at both instructions.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+/// A compiled glob or regular expression.
+pub struct Pattern {
+    insts: Vec<Inst>,
+}
+
+impl Pattern {
+    /// Compiles a glob, which must match the whole text. `*` matches any run of
+    /// characters, `?` any one character, and `[...]` any of a set, such as
+    /// `[a-z]` or `[!0-9]`. `\` matches the next character literally.
+    pub fn glob(pattern: &str) -> Result<Self, String> {
+        let mut parser = Parser { chars: pattern.chars().collect(), pos: 0, depth: 0 };
+        let mut nodes = vec![Node::Start];
+        while let Some(c) = parser.next() {
+            nodes.push(match c {
+                '*' => Node::Repeat { node: Box::new(Node::Any), min: 0, max: None },
+                '?' => Node::Any,
+                '\\' => Node::Char(parser.next().unwrap_or('\\')),
+                // Without a closing `]`, as in `operator[`, it's literal
+                '[' => match parser.class('!')? {
+                    Some(class) => Node::Class(class),
+                    None => Node::Char('['),
+                },
+                c => Node::Char(c),
+            });
+        }
+        nodes.push(Node::End);
+        Pattern::compile(&Node::Concat(nodes))
+    }
+
+    /// Compiles a regular expression, which may match anywhere in the text.
+    /// Supported are `.`, `[...]`, `^`, `$`, `|`, groups, the repetitions `*`,
+    /// `+`, `?` and `{n,m}`, and the classes `\d`, `\w` and `\s`.
+    pub fn regex(pattern: &str) -> Result<Self, String> {
+        let mut parser = Parser { chars: pattern.chars().collect(), pos: 0, depth: 0 };
+        let node = parser.alternate()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("unmatched `)`"));
+        }
+        Pattern::compile(&node)
+    }
+
+    fn compile(node: &Node) -> Result<Self, String> {
+        let mut pattern = Pattern { insts: Vec::new() };
+        pattern.emit(node)?;
+        pattern.insts.push(Inst::Match);
+        Ok(pattern)
+    }
+
+    fn push(&mut self, inst: Inst) -> Result<usize, String> {
+        if self.insts.len() >= MAX_INSTRUCTIONS {
+            return Err("pattern is too large".to_string());
+        }
+        self.insts.push(inst);
+        Ok(self.insts.len() - 1)
+    }
+
+    fn emit(&mut self, node: &Node) -> Result<(), String> {
+        match node {
+            Node::Char(c) => {
+                self.push(Inst::Char(*c))?;
+            }
+            Node::Any => {
+                self.push(Inst::Any)?;
+            }
+            Node::Class(class) => {
+                self.push(Inst::Class(class.clone()))?;
+            }
+            Node::Start => {
+                self.push(Inst::Start)?;
+            }
+            Node::End => {
+                self.push(Inst::End)?;
+            }
+            Node::Concat(nodes) => {
+                for node in nodes {
+                    self.emit(node)?;
+                }
+            }
+            Node::Alternate(nodes) => {
+                let mut jumps = Vec::new();
+                for (i, node) in nodes.iter().enumerate() {
+                    if i + 1 == nodes.len() {
+                        self.emit(node)?;
+                        break;
+                    }
+                    let split = self.push(Inst::Split(0, 0))?;
+                    self.emit(node)?;
+                    jumps.push(self.push(Inst::Jump(0))?);
+                    self.insts[split] = Inst::Split(split + 1, self.insts.len());
+                }
+                for jump in jumps {
+                    self.insts[jump] = Inst::Jump(self.insts.len());
+                }
+            }
+            Node::Repeat { node, min, max } => {
+                for _ in 0..*min {
+                    self.emit(node)?;
+                }
+                match max {
+                    None => {
+                        let split = self.push(Inst::Split(0, 0))?;
+                        self.emit(node)?;
+                        self.push(Inst::Jump(split))?;
+                        self.insts[split] = Inst::Split(split + 1, self.insts.len());
+                    }
+                    Some(max) => {
+                        for _ in *min..*max {
+                            let split = self.push(Inst::Split(0, 0))?;
+                            self.emit(node)?;
+                            self.insts[split] = Inst::Split(split + 1, self.insts.len());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the pattern matches `text`.
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars = text.chars().collect::<Vec<_>>();
+        // The position each instruction was last added at, so that each is
+        // followed once per position
+        let mut seen = vec![usize::MAX; self.insts.len()];
+        let (mut threads, mut next) = (Vec::new(), Vec::new());
+        for pos in 0..=chars.len() {
+            // Start a match at every position
+            if self.add(&mut threads, &mut seen, 0, pos, chars.len()) {
+                return true;
+            }
+            let c = match chars.get(pos) {
+                Some(&c) => c,
+                None => break,
+            };
+            for &pc in &threads {
+                let matched = match &self.insts[pc] {
+                    Inst::Char(expected) => *expected == c,
+                    Inst::Any => true,
+                    Inst::Class(class) => class.matches(c),
+                    _ => false,
+                };
+                if matched && self.add(&mut next, &mut seen, pc + 1, pos + 1, chars.len()) {
+                    return true;
+                }
+            }
+            std::mem::swap(&mut threads, &mut next);
+            next.clear();
+        }
+        false
+    }
+
+    /// Adds the thread at `pc` to `threads`, following jumps, splits and
+    /// assertions. Returns whether it reaches a match.
+    fn add(
+        &self,
+        threads: &mut Vec<usize>,
+        seen: &mut [usize],
+        pc: usize,
+        pos: usize,
+        len: usize,
+    ) -> bool {
+        let mut stack = vec![pc];
+        while let Some(pc) = stack.pop() {
+            if seen[pc] == pos {
+                continue;
+            }
+            seen[pc] = pos;
+            match self.insts[pc] {
+                Inst::Jump(to) => stack.push(to),
+                Inst::Split(a, b) => stack.extend([b, a]),
+                Inst::Start if pos == 0 => stack.push(pc + 1),
+                Inst::End if pos == len => stack.push(pc + 1),
+                Inst::Start | Inst::End => {}
+                Inst::Match => return true,
+                Inst::Char(_) | Inst::Any | Inst::Class(_) => threads.push(pc),
+            }
+        }
+        false
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> { self.chars.get(self.pos).copied() }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn error(&self, message: &str) -> String { format!("{message} at offset {}", self.pos) }
+
+    fn alternate(&mut self) -> Result<Node, String> {
+        let mut nodes = vec![self.concat()?];
+        while self.eat('|') {
+            nodes.push(self.concat()?);
+        }
+        Ok(if nodes.len() == 1 { nodes.remove(0) } else { Node::Alternate(nodes) })
+    }
+
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let node = self.atom()?;
+            nodes.push(self.repeat(node)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    /// Parses any repetitions of `node`, such as `*` or `{2,4}`.
+    fn repeat(&mut self, mut node: Node) -> Result<Node, String> {
+        loop {
+            let start = self.pos;
+            let (min, max) = match self.next() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => match self.counts()? {
+                    Some(counts) => counts,
+                    None => {
+                        self.pos = start;
+                        return Ok(node);
+                    }
+                },
+                _ => {
+                    self.pos = start;
+                    return Ok(node);
+                }
+            };
+            if matches!(node, Node::Start | Node::End | Node::Repeat { .. }) {
+                self.pos = start;
+                return Err(self.error("nothing to repeat"));
+            }
+            // Lazy repetitions match the same texts
+            self.eat('?');
+            node = Node::Repeat { node: Box::new(node), min, max };
+        }
+    }
+
+    /// Parses the counts of `{n}`, `{n,}` or `{n,m}` after the `{`, returning
+    /// `None` if it isn't a repetition and the `{` is literal.
+    fn counts(&mut self) -> Result<Option<(u32, Option<u32>)>, String> {
+        let number = |parser: &mut Parser| {
+            let start = parser.pos;
+            while parser.peek().map_or(false, |c| c.is_ascii_digit()) {
+                parser.pos += 1;
+            }
+            parser.chars[start..parser.pos].iter().collect::<String>().parse::<u32>().ok()
+        };
+        let min = match number(self) {
+            Some(min) => min,
+            None => return Ok(None),
+        };
+        let max = if self.eat(',') { number(self) } else { Some(min) };
+        if !self.eat('}') {
+            return Ok(None);
+        }
+        if max.map_or(min, |max| max.max(min)) > 1000 {
+            return Err(self.error("repetition count is too large"));
+        }
+        if max.map_or(false, |max| max < min) {
+            return Err(self.error("repetition counts are out of order"));
+        }
+        Ok(Some((min, max)))
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = match self.next() {
+            Some(c) => c,
+            None => return Err(self.error("unexpected end of pattern")),
+        };
+        Ok(match c {
+            '(' => {
+                if self.depth >= MAX_DEPTH {
+                    return Err(self.error("groups are nested too deeply"));
+                }
+                // Groups don't capture, so `(?:` is the same
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                self.depth += 1;
+                let node = self.alternate()?;
+                self.depth -= 1;
+                if !self.eat(')') {
+                    return Err(self.error("unclosed group"));
+                }
+                node
+            }
+            '[' => match self.class('^')? {
+                Some(class) => Node::Class(class),
+                None => return Err(self.error("unclosed `[`")),
+            },
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '\\' => match self.next() {
+                Some(c) => match escape_class(c) {
+                    Some(class) => Node::Class(class),
+                    None if c.is_ascii_alphanumeric() && !matches!(c, 'n' | 't' | 'r') => {
+                        self.pos -= 1;
+                        return Err(self.error("unsupported escape"));
+                    }
+                    None => Node::Char(unescape(c)),
+                },
+                None => return Err(self.error("trailing `\\`")),
+            },
+            '*' | '+' | '?' => {
+                self.pos -= 1;
+                return Err(self.error("nothing to repeat"));
+            }
+            c => Node::Char(c),
+        })
+    }
+
+    /// Parses a set of characters after the `[`, which is negated by a leading
+    /// `negate`. Returns `None`, leaving the position unchanged, if there's no
+    /// closing `]`.
+    fn class(&mut self, negate: char) -> Result<Option<Class>, String> {
+        let start = self.pos;
+        let negated = self.eat(negate);
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                Some(']') if !first => return Ok(Some(Class { ranges, negated })),
+                Some('\\') if negate == '^' => match self.next() {
+                    Some(c) => match escape_class(c) {
+                        Some(class) if !class.negated => {
+                            ranges.extend(class.ranges);
+                            first = false;
+                            continue;
+                        }
+                        Some(_) => {
+                            self.pos -= 1;
+                            return Err(self.error("negated classes can't be used in `[...]`"));
+                        }
+                        None => unescape(c),
+                    },
+                    None => break,
+                },
+                Some(c) => c,
+                None => break,
+            };
+            first = false;
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).map_or(false, |&c| c != ']')
+            {
+                self.pos += 1;
+                let end = self.next().unwrap_or(c);
+                if end < c {
+                    return Err(self.error("range is out of order"));
+                }
+                ranges.push((c, end));
+            } else {
+                ranges.push((c, c));
+            }
+        }
+        self.pos = start;
+        Ok(None)
+    }
+}
+
+/// The class for an escape such as `\d`, if it is one.
+fn escape_class(c: char) -> Option<Class> {
+    const DIGIT: &[(char, char)] = &[('0', '9')];
+    const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+    const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+    match c {
+        'd' | 'D' => Some(Class::new(DIGIT, c == 'D')),
+        'w' | 'W' => Some(Class::new(WORD, c == 'W')),
+        's' | 'S' => Some(Class::new(SPACE, c == 'S')),
+        _ => None,
+    }
+}
+
+fn unescape(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str, text: &str) -> bool { Pattern::glob(pattern).unwrap().is_match(text) }
+
+    fn regex(pattern: &str, text: &str) -> bool { Pattern::regex(pattern).unwrap().is_match(text) }
+
+    fn regex_error(pattern: &str) -> String { Pattern::regex(pattern).err().unwrap() }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob("CActor::*", "CActor::Think(float)"));
+        assert!(!glob("CActor::*", "xCActor::Think(float)"));
+        assert!(glob("*(*float*)", "CActor::Think(float, CStateManager&)"));
+        assert!(!glob("*(*float*)", "CActor::Think(int)"));
+        assert!(glob("?", "a"));
+        assert!(!glob("?", "ab"));
+        assert!(!glob("?", ""));
+        assert!(glob("[a-c]x", "bx"));
+        assert!(!glob("[a-c]x", "dx"));
+        assert!(glob("[!a-c]x", "dx"));
+        assert!(!glob("[!a-c]x", "ax"));
+        assert!(glob("[]]", "]"));
+        assert!(glob("\\*", "*"));
+        assert!(!glob("\\*", "a"));
+        // An unclosed `[` is literal
+        assert!(glob("*::operator[]", "CVector::operator[]"));
+        assert!(glob("*", ""));
+        assert!(glob("", ""));
+        assert!(!glob("", "a"));
+        assert_eq!(Pattern::glob("[z-a]").err().unwrap(), "range is out of order at offset 4");
+    }
+
+    #[test]
+    fn test_regex() {
+        assert!(regex("Think", "CActor::Think()"));
+        assert!(regex("", "anything"));
+        assert!(regex("^CActor", "CActor::Think()"));
+        assert!(!regex("^CActor", "xCActor::Think()"));
+        assert!(regex("const$", "CActor::GetName() const"));
+        assert!(!regex("const$", "CActor::GetName(const char*)"));
+        assert!(regex("^$", ""));
+        assert!(regex("^(Think|Update)$", "Update"));
+        assert!(!regex("^(Think|Update)$", "Render"));
+        assert!(regex("^(?:ab)+$", "abab"));
+        assert!(!regex("^(ab)+$", "aba"));
+        assert!(regex("^a.c$", "abc"));
+        assert!(regex("^a\\.c$", "a.c"));
+        assert!(!regex("^a\\.c$", "abc"));
+        assert!(regex("^colou?r$", "color"));
+        assert!(regex("^a*$", ""));
+        assert!(regex("^a+?$", "aaa"));
+        assert!(regex("^a{2}$", "aa"));
+        assert!(!regex("^a{2}$", "aaa"));
+        assert!(regex("^a{2,3}$", "aaa"));
+        assert!(!regex("^a{2,3}$", "aaaa"));
+        assert!(regex("^a{2,}$", "aaaaa"));
+        assert!(!regex("^a{2,}$", "a"));
+        // A `{` that doesn't start counts is literal
+        assert!(regex("^a{x}$", "a{x}"));
+        assert!(regex("^\\d+$", "123"));
+        assert!(!regex("^\\d+$", "12a"));
+        assert!(regex("^\\w+\\s\\W$", "x_1 &"));
+        assert!(regex("^[\\d_]+$", "1_2"));
+        assert!(regex("^[^a-c]$", "d"));
+        assert!(!regex("^[^a-c]$", "b"));
+        assert!(regex("a\\tb", "a\tb"));
+        assert!(regex("^operator\\[\\]$", "operator[]"));
+    }
+
+    #[test]
+    fn test_regex_errors() {
+        assert_eq!(regex_error("(a"), "unclosed group at offset 2");
+        assert_eq!(regex_error("a)"), "unmatched `)` at offset 1");
+        assert_eq!(regex_error("[a"), "unclosed `[` at offset 1");
+        assert_eq!(regex_error("[z-a]"), "range is out of order at offset 4");
+        assert_eq!(regex_error("[\\D]"), "negated classes can't be used in `[...]` at offset 2");
+        assert_eq!(regex_error("a{3,2}"), "repetition counts are out of order at offset 6");
+        assert_eq!(regex_error("a{1001}"), "repetition count is too large at offset 7");
+        assert_eq!(regex_error("*a"), "nothing to repeat at offset 0");
+        assert_eq!(regex_error("^*"), "nothing to repeat at offset 1");
+        assert_eq!(regex_error("a**"), "nothing to repeat at offset 2");
+        assert_eq!(regex_error("\\q"), "unsupported escape at offset 1");
+        assert_eq!(regex_error("a\\"), "trailing `\\` at offset 2");
+        assert_eq!(regex_error(&"(".repeat(100)), "groups are nested too deeply at offset 65");
+        assert_eq!(regex_error("(a{1000}){1000}"), "pattern is too large");
+    }
+}
@@ -0,0 +1,51 @@
+//! Reading whole symbol tables, for subcommands that summarise or search them.
+
+use cwdemangle::{parse_map, MapEntry};
+
+use crate::{
+    argh_cargo::STDIN,
+    elf::{is_archive, is_elf, read_archive},
+    read_input,
+};
+
+/// Reads the symbol names in a file, or stdin for `-`. The file can be an ELF
+/// object or `ar` archive, whose undefined symbols are skipped, a CodeWarrior
+/// linker map, or a list of symbols, one per line.
+pub fn read_symbols(path: &str) -> Result<Vec<String>, String> {
+    let data = read_input(path)?;
+    let name = if path == STDIN { "stdin" } else { path };
+    let mut symbols = Vec::new();
+    if is_archive(&data) {
+        for (member, data) in read_archive(&data).map_err(|e| format!("{name}: {e}"))? {
+            if is_elf(data) {
+                read_elf(data, &mut symbols).map_err(|e| format!("{name}({member}): {e}"))?;
+            }
+        }
+    } else if is_elf(&data) {
+        read_elf(&data, &mut symbols).map_err(|e| format!("{name}: {e}"))?;
+    } else {
+        let text = String::from_utf8_lossy(&data);
+        // Only the section layout of a map is used, as the link tree lists
+        // the same symbols again
+        symbols.extend(parse_map(&text).filter_map(|line| match line.entry {
+            MapEntry::Symbol { name, .. } if !name.starts_with('.') && name != "*fill*" => {
+                Some(name.to_string())
+            }
+            _ => None,
+        }));
+        if symbols.is_empty() {
+            symbols
+                .extend(text.lines().map(str::trim).filter(|l| !l.is_empty()).map(str::to_string));
+        }
+    }
+    Ok(symbols)
+}
+
+fn read_elf(data: &[u8], symbols: &mut Vec<String>) -> Result<(), String> {
+    for symbol in crate::elf::read_symbols(data)? {
+        if symbol.section != b"UND" && !symbol.name.is_empty() {
+            symbols.push(String::from_utf8_lossy(symbol.name).into_owned());
+        }
+    }
+    Ok(())
+}
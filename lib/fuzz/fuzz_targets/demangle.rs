@@ -0,0 +1,48 @@
+#![no_main]
+
+use cwdemangle::{
+    demangle, demangle_bytes, demangle_parts, demangle_spanned, demangle_tokens, explain, mangle,
+    DemangleOptions, Encoding,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for omit_empty_parameters in [false, true] {
+        for mw_extensions in [false, true] {
+            let options = DemangleOptions { omit_empty_parameters, mw_extensions };
+            for encoding in [Encoding::Utf8, Encoding::ShiftJis, Encoding::Escape] {
+                demangle_bytes(data, encoding, &options);
+            }
+            if let Ok(str) = std::str::from_utf8(data) {
+                let spanned = demangle_spanned(str, &options);
+                assert_eq!(
+                    spanned.as_ref().map(|s| &s.demangled),
+                    demangle(str, &options).as_ref()
+                );
+                for (output, input, _) in spanned.iter().flat_map(|s| &s.spans) {
+                    assert!(spanned.as_ref().unwrap().demangled.get(output.clone()).is_some());
+                    assert!(input.end <= str.len() && input.start <= input.end);
+                }
+                let parts = demangle_parts(str, &options);
+                assert_eq!(parts.as_ref().map(|p| &p.demangled), demangle(str, &options).as_ref());
+                if let Some(parts) = &parts {
+                    assert!(parts.diff(parts).is_empty());
+                }
+                let explanation = explain(str, &options);
+                assert_eq!(explanation.demangled, demangle(str, &options));
+                for step in &explanation.steps {
+                    assert!(step.input.start <= step.input.end && step.input.end <= str.len());
+                }
+                if let Err(e) = mangle(str) {
+                    assert!(e.position <= str.len());
+                }
+                if let Some(demangled) = demangle(str, &options) {
+                    let _ = mangle(&demangled);
+                }
+                if let Some(tokens) = demangle_tokens(str, &options) {
+                    assert_eq!(tokens.iter().map(|(_, s)| s).collect::<String>(), tokens.demangled);
+                }
+            }
+        }
+    }
+});
@@ -0,0 +1,183 @@
+//! Recognizing decorations that other tools add around mangled names.
+
+use alloc::{string::String, vec};
+
+use crate::{demangle, DemangleOptions};
+
+/// A decoration that another tool may add around a mangled name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Affix<'a> {
+    /// A literal prefix, such as `fn_`.
+    Prefix(&'a str),
+    /// A separator followed by a decimal number, such as `.1234`.
+    NumericSuffix(&'a str),
+    /// A separator followed by an 8-digit hexadecimal address, such as `_80045A3C`.
+    AddressSuffix(&'a str),
+}
+
+/// Affixes recognized by default:
+/// - `lbl_` and `fn_` prefixes from disassemblers
+/// - `.1234` suffixes on ELF local symbols
+/// - `@1234` disambiguation suffixes on duplicate names
+/// - `_80045A3C` and `@80045A3C` address suffixes
+pub const DEFAULT_AFFIXES: &[Affix<'static>] = &[
+    Affix::Prefix("lbl_"),
+    Affix::Prefix("fn_"),
+    Affix::NumericSuffix("."),
+    Affix::NumericSuffix("@"),
+    Affix::AddressSuffix("_"),
+    Affix::AddressSuffix("@"),
+];
+
+/// A mangled name found inside a decorated symbol by [demangle_affixed].
+///
+/// `prefix`, `mangled` and `suffix` concatenate back to the original symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Affixed<'a> {
+    /// Decorations stripped from the start of the symbol.
+    pub prefix: &'a str,
+    /// The undecorated mangled name.
+    pub mangled: &'a str,
+    /// Decorations stripped from the end of the symbol.
+    pub suffix: &'a str,
+    /// The demangled name.
+    pub demangled: String,
+}
+
+impl Affixed<'_> {
+    /// The demangled name with the stripped decorations re-attached.
+    pub fn display(&self) -> String {
+        let mut out = String::from(self.prefix);
+        out += &self.demangled;
+        out += self.suffix;
+        out
+    }
+}
+
+/// Demangle a symbol name that may be decorated with any of `affixes`.
+///
+/// Decorations are stripped one at a time, in the order of `affixes`, for as
+/// long as any match. The most stripped name that demangles is used, so a
+/// decoration is removed even if the name would demangle with it, as
+/// `lbl_Update__6CActorFv` does. Affixes that match nothing, such as an empty
+/// prefix, are ignored.
+///
+/// Returns `None` if no valid mangled name is found.
+pub fn demangle_affixed<'a>(
+    str: &'a str,
+    affixes: &[Affix],
+    options: &DemangleOptions,
+) -> Option<Affixed<'a>> {
+    // Each name with one more decoration stripped, which ends as each strips something
+    let mut candidates = vec![(0, str.len())];
+    let (mut start, mut end) = (0, str.len());
+    while let Some((prefix_len, suffix_len)) = affixes
+        .iter()
+        .filter_map(|a| strip_affix(&str[start..end], *a))
+        .find(|&(prefix_len, suffix_len)| prefix_len + suffix_len > 0)
+    {
+        start += prefix_len;
+        end -= suffix_len;
+        candidates.push((start, end));
+    }
+    candidates.into_iter().rev().find_map(|(start, end)| {
+        let mangled = &str[start..end];
+        let demangled = demangle(mangled, options)?;
+        Some(Affixed { prefix: &str[..start], mangled, suffix: &str[end..], demangled })
+    })
+}
+
+/// Returns the lengths of the prefix and suffix matched by `affix`, if any.
+fn strip_affix(str: &str, affix: Affix) -> Option<(usize, usize)> {
+    match affix {
+        Affix::Prefix(prefix) => {
+            let rest = str.strip_prefix(prefix).filter(|rest| !rest.is_empty())?;
+            Some((str.len() - rest.len(), 0))
+        }
+        Affix::NumericSuffix(separator) => {
+            let rest = str.trim_end_matches(|c: char| c.is_ascii_digit());
+            if rest.len() == str.len() {
+                return None;
+            }
+            let rest = rest.strip_suffix(separator).filter(|rest| !rest.is_empty())?;
+            Some((0, str.len() - rest.len()))
+        }
+        Affix::AddressSuffix(separator) => {
+            let len = separator.len() + 8;
+            let split = str.len().checked_sub(len).filter(|&split| split > 0)?;
+            let address = str.get(split..)?.strip_prefix(separator)?;
+            if !address.bytes().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            Some((0, len))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn test_demangle_affixed() {
+        let options = DemangleOptions::default();
+        let affixed = |str| demangle_affixed(str, DEFAULT_AFFIXES, &options);
+        assert_eq!(
+            affixed("Update__6CActorFv"),
+            Some(Affixed {
+                prefix: "",
+                mangled: "Update__6CActorFv",
+                suffix: "",
+                demangled: "CActor::Update()".to_string(),
+            })
+        );
+        assert_eq!(
+            affixed("Update__6CActorFv.1234"),
+            Some(Affixed {
+                prefix: "",
+                mangled: "Update__6CActorFv",
+                suffix: ".1234",
+                demangled: "CActor::Update()".to_string(),
+            })
+        );
+        assert_eq!(
+            affixed("fn_Update__6CActorFv@12_80045A3C").map(|a| (a.prefix, a.suffix, a.display())),
+            Some(("fn_", "@12_80045A3C", "fn_CActor::Update()@12_80045A3C".to_string()))
+        );
+        assert_eq!(
+            affixed("lbl___dt__6CActorFv@80045a3c").map(|a| (a.prefix, a.mangled, a.suffix)),
+            Some(("lbl_", "__dt__6CActorFv", "@80045a3c"))
+        );
+        // Names that only look decorated are left alone
+        assert_eq!(
+            affixed("IsEqual<i>__FRCiRCi_b").map(|a| (a.suffix, a.demangled)),
+            Some(("", "bool IsEqual<int>(const int&, const int&)".to_string()))
+        );
+        // Decorations are stripped even if the name demangles with them
+        assert_eq!(
+            affixed("lbl_Update__6CActorFv").map(|a| (a.prefix, a.demangled)),
+            Some(("lbl_", "CActor::Update()".to_string()))
+        );
+        // Bare numbers aren't types outside template arguments
+        assert_eq!(demangle("Update__6CActorFv_80001234", &options), None);
+        assert_eq!(
+            affixed("Update__6CActorFv_80001234").map(|a| (a.suffix, a.demangled)),
+            Some(("_80001234", "CActor::Update()".to_string()))
+        );
+        assert_eq!(affixed("lbl_80045A3C"), None);
+        assert_eq!(affixed(".1234"), None);
+        assert_eq!(
+            demangle_affixed("Update__6CActorFv.1234", &[Affix::Prefix("fn_")], &options),
+            None
+        );
+        // Empty affixes match without stripping anything, and are skipped
+        let empty = [Affix::Prefix(""), Affix::NumericSuffix(""), Affix::Prefix("fn_")];
+        assert_eq!(
+            demangle_affixed("fn_Update__6CActorFv", &empty, &options).map(|a| a.prefix),
+            Some("fn_")
+        );
+        assert_eq!(demangle_affixed("garbage", &empty, &options), None);
+    }
+}
@@ -0,0 +1,266 @@
+//! Comparing demangled symbols part by part.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{Parts, TypeModifier, TypeParts};
+
+/// Which part of a symbol a [Difference] is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLocation {
+    /// What kind of entity the symbols name.
+    Kind,
+    /// The classes or namespaces the symbols are members of.
+    Class,
+    /// The unqualified names.
+    Name,
+    /// Whether the symbols have parameters at all.
+    Parameters,
+    /// A parameter, numbered from 0 in the first symbol, or in the second if the
+    /// first has no parameter there.
+    Parameter(usize),
+    ReturnType,
+    /// Whether the symbols are `const` member functions.
+    Const,
+    /// The function a local static is declared in.
+    LocalFunction,
+    /// The name of a local static.
+    LocalVariable,
+    /// The index of a local static.
+    LocalIndex,
+    /// Whether a local static is a guard variable.
+    Guard,
+}
+
+/// A difference between two symbols, as returned by [Parts::diff].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    pub location: DiffLocation,
+    /// The part in the first symbol, or `None` if it doesn't have one.
+    pub left: Option<String>,
+    /// The part in the second symbol, or `None` if it doesn't have one.
+    pub right: Option<String>,
+    /// How two types differ, if it's more specific than them being different,
+    /// such as `const-qualification differs`.
+    pub note: Option<String>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = match self.location {
+            DiffLocation::Const => return write!(f, "method const-qualification differs"),
+            DiffLocation::Parameter(index) => {
+                write!(f, "parameter {}", index + 1)?;
+                ""
+            }
+            DiffLocation::Kind => "kind",
+            DiffLocation::Class => "class",
+            DiffLocation::Name => "name",
+            DiffLocation::Parameters => "parameters",
+            DiffLocation::ReturnType => "return type",
+            DiffLocation::LocalFunction => "enclosing function",
+            DiffLocation::LocalVariable => "local variable",
+            DiffLocation::LocalIndex => "local index",
+            DiffLocation::Guard => "guard variable",
+        };
+        write!(f, "{location}: ")?;
+        for (i, part) in [&self.left, &self.right].into_iter().enumerate() {
+            if i > 0 {
+                write!(f, " vs ")?;
+            }
+            match part {
+                Some(part) => write!(f, "`{part}`")?,
+                None => write!(f, "none")?,
+            }
+        }
+        if let Some(note) = &self.note {
+            write!(f, " ({note})")?;
+        }
+        Ok(())
+    }
+}
+
+impl Parts {
+    /// Compares two symbols part by part, such as each of their parameters,
+    /// returning their differences in order.
+    pub fn diff(&self, other: &Parts) -> Vec<Difference> {
+        let mut diffs = Vec::new();
+        let type_parts = |parts: &'_ Parts, location| match location {
+            DiffLocation::Parameter(index) => parts.parameter_types.as_ref()?.get(index).cloned(),
+            DiffLocation::ReturnType => parts.return_type_parts.clone(),
+            _ => None,
+        };
+        let mut push = |location, left: Option<&str>, right: Option<&str>| {
+            if left != right {
+                let note = match (type_parts(self, location), type_parts(other, location)) {
+                    (Some(a), Some(b)) => type_note(&a, &b),
+                    _ => None,
+                };
+                let (left, right) = (left.map(String::from), right.map(String::from));
+                diffs.push(Difference { location, left, right, note });
+            }
+        };
+        push(DiffLocation::Kind, Some(self.kind.as_str()), Some(other.kind.as_str()));
+        let class =
+            |parts: &Parts| Some(parts.class_path.join("::")).filter(|class| !class.is_empty());
+        push(DiffLocation::Class, class(self).as_deref(), class(other).as_deref());
+        push(DiffLocation::Name, Some(&self.name), Some(&other.name));
+        match (&self.parameters, &other.parameters) {
+            (Some(left), Some(right)) => diff_parameters(left, right, &mut push),
+            (left, right) => {
+                let list = |params: &Option<Vec<String>>| {
+                    params.as_ref().map(|params| format!("({})", params.join(", ")))
+                };
+                push(DiffLocation::Parameters, list(left).as_deref(), list(right).as_deref());
+            }
+        }
+        push(DiffLocation::ReturnType, self.return_type.as_deref(), other.return_type.as_deref());
+        if self.is_const != other.is_const {
+            push(
+                DiffLocation::Const,
+                Some(if self.is_const { "const" } else { "" }),
+                Some(if other.is_const { "const" } else { "" }),
+            );
+        }
+        if let (Some(left), Some(right)) = (&self.local_static, &other.local_static) {
+            push(DiffLocation::LocalFunction, Some(&left.function), Some(&right.function));
+            push(DiffLocation::LocalVariable, left.variable.as_deref(), right.variable.as_deref());
+            let (a, b) = (left.index.map(|i| i.to_string()), right.index.map(|i| i.to_string()));
+            push(DiffLocation::LocalIndex, a.as_deref(), b.as_deref());
+            let guard = |guard| Some(if guard { "guard" } else { "variable" });
+            push(DiffLocation::Guard, guard(left.is_guard), guard(right.is_guard));
+        }
+        diffs
+    }
+}
+
+/// Compares two parameter lists. Parameters matching at the start and end are
+/// skipped first, so that one added or removed parameter is reported alone.
+fn diff_parameters(
+    left: &[String],
+    right: &[String],
+    push: &mut impl FnMut(DiffLocation, Option<&str>, Option<&str>),
+) {
+    let prefix = left.iter().zip(right).take_while(|(a, b)| a == b).count();
+    let suffix = left[prefix..]
+        .iter()
+        .rev()
+        .zip(right[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (left, right) = (&left[prefix..left.len() - suffix], &right[prefix..right.len() - suffix]);
+    for i in 0..left.len().max(right.len()) {
+        let (a, b) = (left.get(i).map(String::as_str), right.get(i).map(String::as_str));
+        push(DiffLocation::Parameter(prefix + i), a, b);
+    }
+}
+
+/// Describes how the types `a` and `b` differ, if it's only in their modifiers
+/// or only in the type they modify.
+fn type_note(a: &TypeParts, b: &TypeParts) -> Option<String> {
+    if a.base != b.base {
+        // Unmodified types need no more explanation
+        if a.modifiers != b.modifiers || a.modifiers.is_empty() {
+            return None;
+        }
+        return Some(format!("underlying type differs: `{}` vs `{}`", a.base, b.base));
+    }
+    let without = |modifiers: &[TypeModifier], skip: &[TypeModifier]| {
+        modifiers.iter().filter(|m| !skip.contains(m)).copied().collect::<Vec<_>>()
+    };
+    let same_without =
+        |skip: &[TypeModifier]| without(&a.modifiers, skip) == without(&b.modifiers, skip);
+    let indirection = |modifiers: &[TypeModifier]| {
+        let pointer = |m: &TypeModifier| match m {
+            TypeModifier::Reference => TypeModifier::Pointer,
+            m => *m,
+        };
+        modifiers.iter().map(pointer).collect::<Vec<_>>()
+    };
+    let note = if same_without(&[TypeModifier::Const]) {
+        "const-qualification differs"
+    } else if same_without(&[TypeModifier::Volatile]) {
+        "volatile-qualification differs"
+    } else if indirection(&a.modifiers) == indirection(&b.modifiers) {
+        let first = a.modifiers.iter().zip(&b.modifiers).find(|(a, b)| a != b);
+        match first {
+            Some((TypeModifier::Reference, _)) => "reference vs pointer",
+            _ => "pointer vs reference",
+        }
+    } else if same_without(&[TypeModifier::Const, TypeModifier::Volatile]) {
+        "qualifiers differ"
+    } else {
+        "indirection differs"
+    };
+    Some(note.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{demangle_parts, DemangleOptions};
+
+    #[test]
+    fn test_diff() {
+        let options = DemangleOptions::default();
+        let diff = |a, b| {
+            let (a, b) =
+                (demangle_parts(a, &options).unwrap(), demangle_parts(b, &options).unwrap());
+            a.diff(&b).iter().map(|d| d.to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            diff("Set__6CActorFR9CVector3f", "Set__6CActorFR9CVector3f"),
+            Vec::<String>::new()
+        );
+        assert_eq!(diff("Set__6CActorFiR9CVector3f", "Set__6CActorFiRC9CVector3f"), vec![
+            "parameter 2: `CVector3f&` vs `const CVector3f&` (const-qualification differs)"
+        ]);
+        assert_eq!(diff("Get__6CActorFv", "Get__6CActorCFv"), vec![
+            "method const-qualification differs"
+        ]);
+        assert_eq!(diff("Set__6CActorFPCi", "Set__7CEntityFRCl"), vec![
+            "class: `CActor` vs `CEntity`",
+            "parameter 1: `const int*` vs `const long&`",
+        ]);
+        assert_eq!(diff("Set__6CActorFPCi", "Set__6CActorFPPCi"), vec![
+            "parameter 1: `const int*` vs `const int**` (indirection differs)"
+        ]);
+        assert_eq!(diff("Set__6CActorFPCi", "Set__6CActorFRCi"), vec![
+            "parameter 1: `const int*` vs `const int&` (pointer vs reference)"
+        ]);
+        assert_eq!(diff("Set__6CActorFRCi", "Set__6CActorFRCl"), vec![
+            "parameter 1: `const int&` vs `const long&` (underlying type differs: `int` vs `long`)"
+        ]);
+        assert_eq!(diff("Set__6CActorFifb", "Set__6CActorFiUcfb"), vec![
+            "parameter 2: none vs `unsigned char`"
+        ]);
+        assert_eq!(diff("Set__6CActorFv", "mSet__6CActor"), vec![
+            "kind: `function` vs `variable`",
+            "name: `Set` vs `mSet`",
+            "parameters: `()` vs none",
+        ]);
+        assert_eq!(diff("IsEqual<i>__FRCiRCi_b", "IsEqual<i>__FRCiRCi_i"), vec![
+            "return type: `bool` vs `int`"
+        ]);
+        assert_eq!(diff("Get<i>__FRCi_Pi", "Get<i>__FRCi_PCi"), vec![
+            "return type: `int*` vs `const int*` (const-qualification differs)"
+        ]);
+        assert_eq!(diff("Set__6CActorFPVi", "Set__6CActorFPi"), vec![
+            "parameter 1: `volatile int*` vs `int*` (volatile-qualification differs)"
+        ]);
+        assert_eq!(diff("Set__6CActorFPCi", "Set__6CActorFPVi"), vec![
+            "parameter 1: `const int*` vs `volatile int*` (qualifiers differ)"
+        ]);
+        // Types that only differ in template arguments
+        assert_eq!(diff("Set__FRCQ23std9vector<i>", "Set__FRCQ23std9vector<f>"), vec![
+            "parameter 1: `const std::vector<int>&` vs `const std::vector<float>&` (underlying type \
+             differs: `std::vector<int>` vs `std::vector<float>`)"
+        ]);
+    }
+}
@@ -0,0 +1,150 @@
+//! Recording the steps taken while parsing a mangled name.
+
+use alloc::{string::String, vec::Vec};
+use core::{cell::RefCell, ops::Range};
+
+/// What a parsing step was parsing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+    /// A whole function or variable symbol.
+    Symbol,
+    /// The function or variable name before the `__` separator.
+    FunctionName,
+    /// The `__` separating the function name from the class name and signature.
+    Separator,
+    /// A special function name such as `__ct` or `__opb`, once the class name is
+    /// known.
+    SpecialName,
+    /// A length-prefixed name, such as `6CActor`.
+    Name,
+    /// A `Q`-prefixed qualified name, such as `Q23std6vector`.
+    QualifiedName,
+    /// A run of qualifiers, such as `PC`.
+    Qualifiers,
+    /// A template argument list, such as `<i,3>`.
+    TemplateArguments,
+    /// A type, or a template argument.
+    Type,
+    /// A function or member function type, starting at its `F` or `M`.
+    FunctionType,
+    /// An array type, following its `A`.
+    ArrayType,
+    /// A function's parameter list, starting at its `F`.
+    Parameters,
+    /// The `C` marking a `const` member function.
+    Const,
+    /// A function's return type, starting at its `_`.
+    ReturnType,
+    /// A function-local name, such as `@LOCAL@func__Fv@name`.
+    LocalName,
+    /// A static initializer or destructor name, such as `__sinit_file_cpp`.
+    StaticInitializer,
+}
+
+impl StepKind {
+    /// A short description of the step.
+    pub fn description(self) -> &'static str {
+        match self {
+            StepKind::Symbol => "symbol",
+            StepKind::FunctionName => "function name",
+            StepKind::Separator => "`__` separator",
+            StepKind::SpecialName => "special function name",
+            StepKind::Name => "length-prefixed name",
+            StepKind::QualifiedName => "qualified name (`Q`)",
+            StepKind::Qualifiers => "qualifiers",
+            StepKind::TemplateArguments => "template arguments",
+            StepKind::Type => "type",
+            StepKind::FunctionType => "function type",
+            StepKind::ArrayType => "array type",
+            StepKind::Parameters => "parameters (`F`)",
+            StepKind::Const => "const member function (`C`)",
+            StepKind::ReturnType => "return type (`_`)",
+            StepKind::LocalName => "function-local name",
+            StepKind::StaticInitializer => "static initializer",
+        }
+    }
+}
+
+/// A step taken while parsing a mangled name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub kind: StepKind,
+    /// How many steps this one is nested inside.
+    pub depth: usize,
+    /// The bytes of the mangled name the step consumed. For failed steps, this is
+    /// everything from where the step started.
+    pub input: Range<usize>,
+    /// What the step demangled to, or `None` if it failed.
+    pub output: Option<String>,
+}
+
+/// The steps taken to demangle a symbol, as returned by
+/// [explain](crate::explain).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// Every step in the order it started, including those that failed or were
+    /// abandoned for another interpretation.
+    pub steps: Vec<Step>,
+    /// The demangled name, or `None` if the symbol failed to demangle.
+    pub demangled: Option<String>,
+}
+
+/// Steps recorded so far, along with those still in progress.
+pub(crate) struct Trace {
+    base: usize,
+    steps: Vec<Step>,
+    open: Vec<usize>,
+}
+
+impl Trace {
+    /// Creates a trace of parsing `input`, which all parsed slices must lie within.
+    pub(crate) fn new(input: &[u8]) -> Self {
+        Trace { base: input.as_ptr() as usize, steps: Vec::new(), open: Vec::new() }
+    }
+
+    pub(crate) fn into_steps(self) -> Vec<Step> { self.steps }
+}
+
+/// A step in progress. It's marked as failed unless [end](Self::end) is called
+/// before it's dropped.
+pub(crate) struct StepGuard<'a> {
+    trace: Option<&'a RefCell<Trace>>,
+    index: usize,
+}
+
+impl<'a> StepGuard<'a> {
+    /// Starts a step parsing `str`, if a trace is being recorded.
+    pub(crate) fn new(trace: Option<&'a RefCell<Trace>>, kind: StepKind, str: &[u8]) -> Self {
+        let mut index = 0;
+        if let Some(trace) = trace {
+            let mut trace = trace.borrow_mut();
+            let start = (str.as_ptr() as usize).wrapping_sub(trace.base);
+            index = trace.steps.len();
+            let depth = trace.open.len();
+            trace.steps.push(Step { kind, depth, input: start..start + str.len(), output: None });
+            trace.open.push(index);
+        }
+        StepGuard { trace, index }
+    }
+
+    /// Completes the step, which stopped before `rest` and demangled to the
+    /// concatenation of `output`.
+    pub(crate) fn end(self, rest: &[u8], output: &[&str]) {
+        if let Some(trace) = self.trace {
+            let step = &mut trace.borrow_mut().steps[self.index];
+            step.input.end = step.input.end.saturating_sub(rest.len()).max(step.input.start);
+            step.output = Some(output.concat());
+        }
+    }
+}
+
+impl Drop for StepGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(trace) = self.trace {
+            let mut trace = trace.borrow_mut();
+            if trace.open.last() == Some(&self.index) {
+                trace.open.pop();
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 //! A CodeWarrior C++ symbol demangler.
-//! 
+//!
 //! # Usage
 //! ```
 //! use cwdemangle::{demangle, DemangleOptions};
@@ -15,9 +15,31 @@ use alloc::{
     format,
     str::FromStr,
     string::{String, ToString},
+    vec::Vec,
 };
+use core::{cell::RefCell, mem, ops::Range};
+
+mod affix;
+mod diff;
+mod explain;
+mod mangle;
+mod map;
+mod parts;
+mod sjis;
+mod text;
+
+pub use affix::{demangle_affixed, Affix, Affixed, DEFAULT_AFFIXES};
+pub use diff::{DiffLocation, Difference};
+pub use explain::{Explanation, Step, StepKind};
+use explain::{StepGuard, Trace};
+pub use mangle::{mangle, MangleError};
+pub use map::{parse_map, MapEntry, MapLine, MapLines};
+pub use parts::{Parts, SymbolKind, TypeModifier, TypeParts};
+use text::Text;
+pub use text::{SpanKind, Spanned, TokenKind, Tokens};
 
 /// Options for [demangle].
+#[derive(Debug, Clone, Copy)]
 pub struct DemangleOptions {
     /// Replace `(void)` function parameters with `()`
     pub omit_empty_parameters: bool,
@@ -32,486 +54,1059 @@ impl Default for DemangleOptions {
     fn default() -> Self { DemangleOptions { omit_empty_parameters: true, mw_extensions: false } }
 }
 
-fn parse_qualifiers(mut str: &str) -> (String, String, &str) {
-    let mut pre = String::new();
-    let mut post = String::new();
-    for c in str.chars() {
-        match c {
-            'P' => {
-                if pre.is_empty() {
-                    post.insert(0, '*');
-                } else {
-                    post.insert_str(0, format!("* {}", pre.trim_end()).as_str());
-                    pre.clear();
+/// How [demangle_bytes] interprets non-ASCII bytes in identifiers.
+///
+/// Bytes that can't be decoded are always escaped as `\xNN`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Decode identifiers as UTF-8.
+    Utf8,
+    /// Decode identifiers as Shift-JIS (code page 932), as written by Japanese
+    /// Windows hosts.
+    ShiftJis,
+    /// Escape every non-ASCII byte.
+    Escape,
+}
+
+impl Default for Encoding {
+    fn default() -> Self { Encoding::Utf8 }
+}
+
+/// Maximum nesting depth of types and template arguments.
+///
+/// Real symbols stay far below this; it only exists so that crafted inputs
+/// can't exhaust the stack, even on a 2 MiB thread in an unoptimized build.
+const MAX_DEPTH: u32 = 64;
+
+/// State threaded through the recursive demangling functions.
+#[derive(Clone, Copy)]
+struct Context<'a> {
+    options: &'a DemangleOptions,
+    encoding: Encoding,
+    depth: u32,
+    /// The whole mangled name, if spans are being recorded.
+    input: Option<&'a [u8]>,
+    /// Whether token kinds are being recorded.
+    tokens: bool,
+    /// Where parsing steps are recorded, if they are.
+    trace: Option<&'a RefCell<Trace>>,
+}
+
+impl<'a> Context<'a> {
+    fn new(options: &'a DemangleOptions, encoding: Encoding) -> Self {
+        Context { options, encoding, depth: 0, input: None, tokens: false, trace: None }
+    }
+
+    /// Records spans relative to `input`, which all parsed slices must lie within.
+    fn with_spans(self, input: &'a [u8]) -> Self { Context { input: Some(input), ..self } }
+
+    fn with_tokens(self) -> Self { Context { tokens: true, ..self } }
+
+    fn with_trace(self, trace: &'a RefCell<Trace>) -> Self {
+        Context { trace: Some(trace), ..self }
+    }
+
+    /// Starts a parsing step at the start of `str`, recording it if requested.
+    fn step(self, kind: StepKind, str: &[u8]) -> StepGuard<'a> {
+        StepGuard::new(self.trace, kind, str)
+    }
+
+    /// Creates a token of the given kind, recording the kind if requested.
+    fn token(self, kind: TokenKind, str: &str) -> Text {
+        if self.tokens {
+            Text::token(kind, str)
+        } else {
+            Text::from(str)
+        }
+    }
+
+    /// Records that `text` was produced by the bytes of `start` that precede `rest`.
+    fn mark(self, text: &mut Text, kind: SpanKind, start: &[u8], rest: &[u8]) {
+        if let Some(input) = self.input {
+            let offset = (start.as_ptr() as usize).wrapping_sub(input.as_ptr() as usize);
+            text.span(kind, offset..offset + start.len().saturating_sub(rest.len()));
+        }
+    }
+
+    /// Enters a nested type, failing once [MAX_DEPTH] is exceeded.
+    fn nested(self) -> Option<Self> {
+        if self.depth >= MAX_DEPTH {
+            return None;
+        }
+        Some(Context { depth: self.depth + 1, ..self })
+    }
+
+    /// Decodes an identifier from the mangled name.
+    fn ident(self, str: &[u8]) -> String {
+        match core::str::from_utf8(str) {
+            Ok(str) if str.is_ascii() || self.encoding == Encoding::Utf8 => return str.to_string(),
+            _ => {}
+        }
+        let mut out = String::new();
+        let mut rest = str;
+        while let Some(&b) = rest.first() {
+            let decoded = match self.encoding {
+                _ if b.is_ascii() => Some((b as char, 1)),
+                Encoding::Utf8 => decode_utf8_char(rest),
+                Encoding::ShiftJis => sjis::decode_char(rest),
+                Encoding::Escape => None,
+            };
+            match decoded {
+                Some((c, len)) => {
+                    out.push(c);
+                    rest = &rest[len..];
                 }
-            }
-            'R' => {
-                if pre.is_empty() {
-                    post.insert(0, '&');
-                } else {
-                    post.insert_str(0, format!("& {}", pre.trim_end()).as_str());
-                    pre.clear();
+                None => {
+                    out += &format!("\\x{b:02x}");
+                    rest = &rest[1..];
                 }
             }
-            'C' => pre.push_str("const "),
-            'V' => pre.push_str("volatile "),
-            'U' => pre.push_str("unsigned "),
-            'S' => pre.push_str("signed "),
+        }
+        out
+    }
+}
+
+/// Decodes one UTF-8 character from the start of `bytes`, returning it along with
+/// the number of bytes it occupied.
+fn decode_utf8_char(bytes: &[u8]) -> Option<(char, usize)> {
+    let len = match bytes.first()? {
+        0x00..=0x7F => 1,
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return None,
+    };
+    let c = core::str::from_utf8(bytes.get(..len)?).ok()?.chars().next()?;
+    Some((c, len))
+}
+
+fn parse_qualifiers<'a>(mut str: &'a [u8], cx: Context) -> (Text, Text, &'a [u8]) {
+    let start = str;
+    let mut pre = Text::new();
+    let mut post = Text::new();
+    while let Some(&c) = str.first() {
+        let mut qualifier = match c {
+            b'P' => Text::from("*"),
+            b'R' => Text::from("&"),
+            b'C' => cx.token(TokenKind::Keyword, "const"),
+            b'V' => cx.token(TokenKind::Keyword, "volatile"),
+            b'U' => cx.token(TokenKind::Keyword, "unsigned"),
+            b'S' => cx.token(TokenKind::Keyword, "signed"),
             _ => break,
         };
+        cx.mark(&mut qualifier, SpanKind::Qualifier, str, &str[1..]);
+        if c == b'P' || c == b'R' {
+            if !pre.is_empty() {
+                qualifier += " ";
+                qualifier += mem::take(&mut pre).trim_end();
+            }
+            post = qualifier + post;
+        } else {
+            pre += qualifier;
+            pre += " ";
+        }
         str = &str[1..];
     }
-    post.truncate(post.trim_end().len());
+    let post = post.trim_end();
+    if cx.trace.is_some() && str.len() < start.len() {
+        cx.step(StepKind::Qualifiers, start).end(str, &[pre.as_str(), post.as_str()]);
+    }
     (pre, post, str)
 }
 
-fn parse_digits(str: &str) -> Option<(usize, &str)> {
-    if let Some(idx) = str.find(|c: char| !c.is_ascii_digit()) {
-        Some((usize::from_str(&str[..idx]).ok()?, &str[idx..]))
-    } else {
-        // all digits!
-        Some((usize::from_str(str).ok()?, ""))
-    }
+fn parse_digits(str: &[u8]) -> Option<(usize, &[u8])> {
+    let idx = str.iter().position(|c| !c.is_ascii_digit()).unwrap_or(str.len());
+    let (digits, rest) = str.split_at(idx);
+    Some((usize::from_str(core::str::from_utf8(digits).ok()?).ok()?, rest))
 }
 
-fn demangle_template_args<'a>(
-    mut str: &'a str,
-    options: &DemangleOptions,
-) -> Option<(&'a str, String)> {
-    let tmpl_args = if let Some(start_idx) = str.find('<') {
-        let end_idx = str.rfind('>')?;
-        if end_idx < start_idx {
-            return None;
-        }
-        let mut args = &str[start_idx + 1..end_idx];
-        str = &str[..start_idx];
-        let mut tmpl_args = "<".to_string();
-        while !args.is_empty() {
-            let (arg, arg_post, rest) = demangle_arg(args, options)?;
-            tmpl_args += arg.as_str();
-            tmpl_args += arg_post.as_str();
-            if rest.is_empty() {
-                break;
-            } else {
-                tmpl_args += ", ";
+/// Splits a name into its base name and demangled template arguments.
+fn demangle_template_args<'a>(str: &'a [u8], cx: Context) -> Option<(&'a [u8], Text)> {
+    match str.iter().position(|&c| c == b'<') {
+        Some(idx) => {
+            let (args, rest) = demangle_template_arg_list(&str[idx..], cx)?;
+            if !rest.is_empty() {
+                return None;
             }
-            args = &rest[1..];
+            Some((&str[..idx], args))
         }
-        tmpl_args += ">";
-        tmpl_args
-    } else {
-        String::new()
-    };
-    Some((str, tmpl_args))
+        None => Some((str, Text::new())),
+    }
 }
 
-fn demangle_name<'a>(str: &'a str, options: &DemangleOptions) -> Option<(String, String, &'a str)> {
-    let (size, rest) = parse_digits(str)?;
-    if rest.len() < size {
-        return None;
+/// Demangles a template argument list starting at its opening `<`.
+///
+/// Arguments are consumed one at a time, so a `>` inside a nested name can't end
+/// the list early.
+fn demangle_template_arg_list<'a>(str: &'a [u8], cx: Context) -> Option<(Text, &'a [u8])> {
+    let step = cx.step(StepKind::TemplateArguments, str);
+    let mut str = str.strip_prefix(b"<")?;
+    let mut tmpl_args = Text::from("<");
+    if let Some(rest) = str.strip_prefix(b">") {
+        step.end(rest, &["<>"]);
+        return Some((Text::from("<>"), rest));
+    }
+    loop {
+        let (arg, arg_post, rest) = demangle_template_arg(str, cx)?;
+        let mut arg = arg + arg_post;
+        cx.mark(&mut arg, SpanKind::TemplateArgument, str, rest);
+        tmpl_args += arg;
+        if let Some(rest) = rest.strip_prefix(b",") {
+            tmpl_args += ", ";
+            str = rest;
+        } else {
+            str = rest.strip_prefix(b">")?;
+            break;
+        }
     }
-    let (name, args) = demangle_template_args(&rest[..size], options)?;
-    Some((name.to_string(), format!("{name}{args}"), &rest[size..]))
+    tmpl_args += ">";
+    step.end(str, &[tmpl_args.as_str()]);
+    Some((tmpl_args, str))
 }
 
-fn demangle_qualified_name<'a>(
-    mut str: &'a str,
-    options: &DemangleOptions,
-) -> Option<(String, String, &'a str)> {
-    if str.starts_with('Q') {
-        if str.len() < 3 {
-            return None;
+/// Demangles a template argument, which may be an integer literal such as `3` or
+/// `-1` as well as a type.
+fn demangle_template_arg<'a>(str: &'a [u8], cx: Context) -> Option<(Text, Text, &'a [u8])> {
+    let (negative, digits) = match str.strip_prefix(b"-") {
+        Some(digits) => (true, digits),
+        None => (false, str),
+    };
+    if let Some((num, rest)) = parse_digits(digits) {
+        // Metrowerks extension types are bare numbers too
+        let is_type = !negative && cx.options.mw_extensions && mw_extension_type(num).is_some();
+        if (rest.starts_with(b",") || rest.starts_with(b">")) && !is_type {
+            let step = cx.step(StepKind::Type, str);
+            let literal = if negative { format!("-{num}") } else { format!("{num}") };
+            let literal = cx.token(TokenKind::Literal, &literal);
+            step.end(rest, &[literal.as_str()]);
+            return Some((literal, Text::new(), rest));
+        }
+    }
+    demangle_arg(str, cx)
+}
+
+/// Demangles a length-prefixed name, whose own token is of the given `kind`.
+fn demangle_name<'a>(
+    str: &'a [u8],
+    kind: TokenKind,
+    cx: Context,
+) -> Option<(String, Text, &'a [u8])> {
+    let step = cx.step(StepKind::Name, str);
+    let (size, rest) = parse_digits(str)?;
+    let (name, rest) = (rest.get(..size)?, rest.get(size..)?);
+    // Classes declared inside a function are scoped to it
+    let (function, name) = match split_local_name(name) {
+        Some(LocalName { function, name: Some(name), guard: false, .. }) => {
+            (demangle_symbol(function, cx.nested()?)?, name)
         }
-        let count = usize::from_str(&str[1..2]).ok()?;
-        str = &str[2..];
+        Some(_) => return None,
+        None => (Text::new(), name),
+    };
+    let (name, args) = demangle_template_args(name, cx)?;
+    let name = cx.ident(name);
+    let component = Text::token(kind, &name) + args;
+    let mut full = if function.is_empty() { component } else { function + "::" + component };
+    cx.mark(&mut full, SpanKind::Name, str, rest);
+    step.end(rest, &[full.as_str()]);
+    Some((name, full, rest))
+}
+
+fn demangle_qualified_name<'a>(mut str: &'a [u8], cx: Context) -> Option<(String, Text, &'a [u8])> {
+    if let Some(rest) = str.strip_prefix(b"Q") {
+        let step = cx.step(StepKind::QualifiedName, str);
+        let count = match rest.first()? {
+            c @ b'1'..=b'9' => (c - b'0') as usize,
+            _ => return None,
+        };
+        str = &rest[1..];
         let mut last_class = String::new();
-        let mut qualified = String::new();
+        let mut qualified = Text::new();
         for i in 0..count {
-            let (class_name, full, rest) = demangle_name(str, options)?;
-            qualified += full.as_str();
+            let kind = if i < count - 1 { TokenKind::Namespace } else { TokenKind::Class };
+            let (class_name, full, rest) = demangle_name(str, kind, cx)?;
+            qualified += full;
             last_class = class_name;
             str = rest;
             if i < count - 1 {
                 qualified += "::";
             }
         }
+        step.end(str, &[qualified.as_str()]);
         Some((last_class, qualified, str))
     } else {
-        demangle_name(str, options)
+        demangle_name(str, TokenKind::Class, cx)
     }
 }
 
-fn demangle_arg<'a>(
-    mut str: &'a str,
-    options: &DemangleOptions,
-) -> Option<(String, String, &'a str)> {
-    // Negative constant
-    if let Some(str) = str.strip_prefix('-') {
-        let (size, rest) = parse_digits(str)?;
-        let out = format!("-{size}");
-        return Some((out.clone(), String::new(), rest));
-    }
-    let mut result = String::new();
-    let (mut pre, mut post, rest) = parse_qualifiers(str);
-    result += pre.as_str();
+/// Demangles a type or template argument, returning the parts that go before
+/// and after a declarator.
+fn demangle_arg<'a>(str: &'a [u8], cx: Context) -> Option<(Text, Text, &'a [u8])> {
+    let cx = cx.nested()?;
+    let step = cx.step(StepKind::Type, str);
+    let (pre, post, rest) = demangle_type(str, cx)?;
+    step.end(rest, &[pre.as_str(), post.as_str()]);
+    Some((pre, post, rest))
+}
+
+fn demangle_type<'a>(mut str: &'a [u8], cx: Context) -> Option<(Text, Text, &'a [u8])> {
+    let (pre, post, rest) = parse_qualifiers(str, cx);
+    let signed = str[..str.len() - rest.len()].iter().any(|&c| c == b'S' || c == b'U');
+    let mut result = pre.clone();
     str = rest;
     // Disambiguate arguments starting with a number
-    if str.starts_with(|c: char| c.is_ascii_digit()) {
+    if str.first().map_or(false, u8::is_ascii_digit) {
         let (num, rest) = parse_digits(str)?;
-        // If the number is followed by a comma or the end of the string, it's a template argument
-        if rest.is_empty() || rest.starts_with(',') {
-            // ...or a Metrowerks extension type
-            if options.mw_extensions {
-                if let Some(t) = match num {
-                    1 => Some("__int128"),
-                    2 => Some("__vec2x32float__"),
-                    _ => None,
-                } {
-                    result += t;
-                    return Some((result, post, rest));
-                }
-            }
-            result += &format!("{num}");
-            result += post.as_str();
-            return Some((result, String::new(), rest));
+        // If the number ends the argument, it's a Metrowerks extension type.
+        // Literals are only valid as template arguments, which are handled by
+        // `demangle_template_arg`.
+        if rest.is_empty() || rest.starts_with(b",") || rest.starts_with(b">") {
+            let t = mw_extension_type(num).filter(|_| cx.options.mw_extensions)?;
+            result += cx.token(TokenKind::BuiltinType, t);
+            return Some((result, post, rest));
         }
-        // Otherwise, it's (probably) the size of a type
-        let (_, qualified, rest) = demangle_name(str, options)?;
-        result += qualified.as_str();
-        result += post.as_str();
-        return Some((result, String::new(), rest));
+        // Otherwise, it's the size of a type
+        let (_, qualified, rest) = demangle_name(str, TokenKind::Class, cx)?;
+        result += qualified;
+        result += post;
+        return Some((result, Text::new(), rest));
     }
     // Handle qualified names
-    if str.starts_with('Q') {
-        let (_, qualified, rest) = demangle_qualified_name(str, options)?;
-        result += qualified.as_str();
-        result += post.as_str();
-        return Some((result, String::new(), rest));
-    }
-    let mut is_member = false;
-    let mut const_member = false;
-    if str.starts_with('M') {
-        is_member = true;
-        let (_, member, rest) = demangle_qualified_name(&str[1..], options)?;
-        pre = format!("{member}::*{pre}");
-        if !rest.starts_with('F') {
+    if str.starts_with(b"Q") {
+        let (_, qualified, rest) = demangle_qualified_name(str, cx)?;
+        result += qualified;
+        result += post;
+        return Some((result, Text::new(), rest));
+    }
+    if str.starts_with(b"M") || str.starts_with(b"F") {
+        return demangle_function_type(str, pre, post, cx);
+    }
+    if let Some(rest) = str.strip_prefix(b"A") {
+        return demangle_array_type(rest, pre, post, cx);
+    }
+    result += match str.first()? {
+        b'_' => return Some((result, Text::new(), rest)),
+        b'e' => Text::from("..."),
+        // Only the integer types can be signed or unsigned
+        &c if signed && !matches!(c, b'c' | b's' | b'i' | b'l' | b'x') => return None,
+        &c => cx.token(TokenKind::BuiltinType, builtin_type(c)?),
+    };
+    result += post;
+    Some((result, Text::new(), &str[1..]))
+}
+
+/// Demangles a function or member function type starting at its `F` or `M`,
+/// given the qualifiers that preceded it.
+fn demangle_function_type<'a>(
+    mut str: &'a [u8],
+    mut pre: Text,
+    mut post: Text,
+    cx: Context,
+) -> Option<(Text, Text, &'a [u8])> {
+    let step = cx.step(StepKind::FunctionType, str);
+    let mut const_member = Text::new();
+    let is_member = str.starts_with(b"M");
+    if is_member {
+        let (_, member, rest) = demangle_qualified_name(&str[1..], cx)?;
+        pre = member + "::*" + pre;
+        if !rest.starts_with(b"F") {
             return None;
         }
         str = rest;
     }
-    if is_member || str.starts_with('F') {
-        str = &str[1..];
-        if is_member {
-            // "const void*, const void*" or "const void*, void*"
-            if str.starts_with("PCvPCv") {
-                const_member = true;
-                str = &str[6..];
-            } else if str.starts_with("PCvPv") {
-                str = &str[5..];
-            } else {
-                return None;
-            }
-        } else if post.starts_with('*') {
-            post = post[1..].trim_start().to_string();
-            pre = format!("*{pre}");
+    str = &str[1..];
+    if is_member {
+        // "const void*, const void*" or "const void*, void*"
+        if str.starts_with(b"PCvPCv") {
+            let mut cnst = cx.token(TokenKind::Keyword, "const");
+            cx.mark(&mut cnst, SpanKind::Qualifier, str, &str[6..]);
+            const_member = Text::from(" ") + cnst;
+            str = &str[6..];
+        } else if str.starts_with(b"PCvPv") {
+            str = &str[5..];
         } else {
             return None;
         }
-        let (args, rest) = demangle_function_args(str, options)?;
-        if !rest.starts_with('_') {
-            return None;
-        }
-        let (ret_pre, ret_post, rest) = demangle_arg(&rest[1..], options)?;
-        let const_str = if const_member { " const" } else { "" };
-        let res_pre = format!("{ret_pre} ({pre}{post}");
-        let res_post = format!(")({args}){const_str}{ret_post}");
-        return Some((res_pre, res_post, rest));
+    } else if post.as_str().starts_with('*') {
+        let (star, rest) = post.split_at(1);
+        post = rest.trim_start();
+        pre = star + pre;
+    } else {
+        return None;
     }
-    if let Some(rest) = str.strip_prefix('A') {
-        let (count, rest) = parse_digits(rest)?;
-        if !rest.starts_with('_') {
-            return None;
-        }
-        let (arg_pre, arg_post, rest) = demangle_arg(&rest[1..], options)?;
-        if !post.is_empty() {
-            post = format!("({post})");
-        }
-        result = format!("{pre}{arg_pre}{post}");
-        let ret_post = format!("[{count}]{arg_post}");
-        return Some((result, ret_post, rest));
-    }
-    result.push_str(match str.chars().next()? {
-        'i' => "int",
-        'b' => "bool",
-        'c' => "char",
-        's' => "short",
-        'l' => "long",
-        'x' => "long long",
-        'f' => "float",
-        'd' => "double",
-        'w' => "wchar_t",
-        'v' => "void",
-        'e' => "...",
-        '1' if options.mw_extensions => "__int128",
-        '2' if options.mw_extensions => "__vec2x32float__",
-        '_' => return Some((result, String::new(), rest)),
+    let (args, rest) = demangle_function_args(str, cx)?;
+    let ret = rest.strip_prefix(b"_")?;
+    let (mut ret_pre, mut ret_post, rest) = demangle_arg(ret, cx)?;
+    cx.mark(&mut ret_pre, SpanKind::ReturnType, ret, rest);
+    cx.mark(&mut ret_post, SpanKind::ReturnType, ret, rest);
+    let res_pre = ret_pre + " (" + pre + post;
+    let res_post = Text::from(")(") + args + ")" + const_member + ret_post;
+    step.end(rest, &[res_pre.as_str(), res_post.as_str()]);
+    Some((res_pre, res_post, rest))
+}
+
+/// Demangles an array type following its `A`, given the qualifiers that preceded
+/// it.
+fn demangle_array_type<'a>(
+    str: &'a [u8],
+    pre: Text,
+    mut post: Text,
+    cx: Context,
+) -> Option<(Text, Text, &'a [u8])> {
+    let step = cx.step(StepKind::ArrayType, str);
+    let (count, rest) = parse_digits(str)?;
+    let rest = rest.strip_prefix(b"_")?;
+    let (arg_pre, arg_post, rest) = demangle_arg(rest, cx)?;
+    if !post.is_empty() {
+        post = Text::from("(") + post + ")";
+    }
+    let count = cx.token(TokenKind::Literal, &format!("{count}"));
+    let ret_post = Text::from("[") + count + "]" + arg_post;
+    let ret_pre = pre + arg_pre + post;
+    step.end(rest, &[ret_pre.as_str(), ret_post.as_str()]);
+    Some((ret_pre, ret_post, rest))
+}
+
+/// Single-letter codes for builtin types. `signed` and `unsigned` variants are
+/// spelled with the `S` and `U` qualifiers, e.g. `Sc` and `Ux`, and only exist
+/// for the integer types.
+///
+/// This is every code CodeWarrior uses for standard types. `__int64` is a
+/// typedef of `long long` and so mangles as `x`. When `wchar_t` isn't a builtin
+/// type (`-wchar_t off`), it's a typedef of an integer type and mangles as that
+/// type instead of `w`. The remaining Metrowerks types are numbered, see
+/// [mw_extension_type].
+fn builtin_type(c: u8) -> Option<&'static str> {
+    Some(match c {
+        b'v' => "void",
+        b'b' => "bool",
+        b'c' => "char",
+        b's' => "short",
+        b'i' => "int",
+        b'l' => "long",
+        b'x' => "long long",
+        b'f' => "float",
+        b'd' => "double",
+        b'r' => "long double",
+        b'w' => "wchar_t",
+        b'e' => "...",
         _ => return None,
-    });
-    result += post.as_str();
-    Some((result, String::new(), &str[1..]))
+    })
+}
+
+/// Metrowerks extension types, which are encoded as bare numbers and so can only
+/// be told apart from template argument literals by context.
+fn mw_extension_type(num: usize) -> Option<&'static str> {
+    match num {
+        1 => Some("__int128"),
+        2 => Some("__vec2x32float__"),
+        _ => None,
+    }
 }
 
-fn demangle_function_args<'a>(
-    mut str: &'a str,
-    options: &DemangleOptions,
-) -> Option<(String, &'a str)> {
-    let mut result = String::new();
+fn demangle_function_args<'a>(mut str: &'a [u8], cx: Context) -> Option<(Text, &'a [u8])> {
+    let mut result = Text::new();
     while !str.is_empty() {
         if !result.is_empty() {
             result += ", ";
         }
-        let (arg, arg_post, rest) = demangle_arg(str, options)?;
-        result += arg.as_str();
-        result += arg_post.as_str();
+        let (arg, arg_post, rest) = demangle_arg(str, cx)?;
+        let mut arg = arg + arg_post;
+        cx.mark(&mut arg, SpanKind::Parameter, str, rest);
+        result += arg;
         str = rest;
-        if str.starts_with('_') || str.starts_with(',') {
+        if str.starts_with(b"_") || str.starts_with(b",") {
             break;
         }
     }
     Some((result, str))
 }
 
-fn demangle_special_function(
-    str: &str,
-    class_name: &str,
-    options: &DemangleOptions,
-) -> Option<String> {
-    if let Some(rest) = str.strip_prefix("op") {
-        let (arg_pre, arg_post, _) = demangle_arg(rest, options)?;
-        return Some(format!("operator {arg_pre}{arg_post}"));
-    }
-    let (op, args) = demangle_template_args(str, options)?;
-    Some(format!(
-        "{}{}",
-        match op {
-            "dt" => return Some(format!("~{class_name}{args}")),
-            "ct" => class_name,
-            "nw" => "operator new",
-            "nwa" => "operator new[]",
-            "dl" => "operator delete",
-            "dla" => "operator delete[]",
-            "pl" => "operator+",
-            "mi" => "operator-",
-            "ml" => "operator*",
-            "dv" => "operator/",
-            "md" => "operator%",
-            "er" => "operator^",
-            "ad" => "operator&",
-            "or" => "operator|",
-            "co" => "operator~",
-            "nt" => "operator!",
-            "as" => "operator=",
-            "lt" => "operator<",
-            "gt" => "operator>",
-            "apl" => "operator+=",
-            "ami" => "operator-=",
-            "amu" => "operator*=",
-            "adv" => "operator/=",
-            "amd" => "operator%=",
-            "aer" => "operator^=",
-            "aad" => "operator&=",
-            "aor" => "operator|=",
-            "ls" => "operator<<",
-            "rs" => "operator>>",
-            "ars" => "operator>>=",
-            "als" => "operator<<=",
-            "eq" => "operator==",
-            "ne" => "operator!=",
-            "le" => "operator<=",
-            "ge" => "operator>=",
-            "aa" => "operator&&",
-            "oo" => "operator||",
-            "pp" => "operator++",
-            "mm" => "operator--",
-            "cm" => "operator,",
-            "rm" => "operator->*",
-            "rf" => "operator->",
-            "cl" => "operator()",
-            "vc" => "operator[]",
-            "vt" => "__vtable",
-            _ => return Some(format!("__{op}{args}")),
-        },
-        args
-    ))
+fn demangle_special_function(op: &[u8], args: Text, class_name: &str, cx: Context) -> Option<Text> {
+    if let Some(rest) = op.strip_prefix(b"op") {
+        if let Some((arg_pre, arg_post, rest)) = demangle_arg(rest, cx) {
+            if rest.is_empty() {
+                let operator = cx.token(TokenKind::Function, "operator");
+                return Some(operator + " " + arg_pre + arg_post + args);
+            }
+        }
+    }
+    let name = match op {
+        b"dt" => return Some(cx.token(TokenKind::Function, &format!("~{class_name}")) + args),
+        b"ct" => class_name,
+        b"nw" => "operator new",
+        b"nwa" => "operator new[]",
+        b"dl" => "operator delete",
+        b"dla" => "operator delete[]",
+        b"pl" => "operator+",
+        b"mi" => "operator-",
+        b"ml" => "operator*",
+        b"dv" => "operator/",
+        b"md" => "operator%",
+        b"er" => "operator^",
+        b"ad" => "operator&",
+        b"or" => "operator|",
+        b"co" => "operator~",
+        b"nt" => "operator!",
+        b"as" => "operator=",
+        b"lt" => "operator<",
+        b"gt" => "operator>",
+        b"apl" => "operator+=",
+        b"ami" => "operator-=",
+        b"amu" => "operator*=",
+        b"adv" => "operator/=",
+        b"amd" => "operator%=",
+        b"aer" => "operator^=",
+        b"aad" => "operator&=",
+        b"aor" => "operator|=",
+        b"ls" => "operator<<",
+        b"rs" => "operator>>",
+        b"ars" => "operator>>=",
+        b"als" => "operator<<=",
+        b"eq" => "operator==",
+        b"ne" => "operator!=",
+        b"le" => "operator<=",
+        b"ge" => "operator>=",
+        b"aa" => "operator&&",
+        b"oo" => "operator||",
+        b"pp" => "operator++",
+        b"mm" => "operator--",
+        b"cm" => "operator,",
+        b"rm" => "operator->*",
+        b"rf" => "operator->",
+        b"cl" => "operator()",
+        b"vc" => "operator[]",
+        b"vt" => "__vtable",
+        _ => {
+            let name = format!("__{}", cx.ident(op));
+            return Some(cx.token(TokenKind::Function, &name) + args);
+        }
+    };
+    // Keep `operator<` and its template arguments from running together
+    let sep = if name.ends_with('<') && !args.is_empty() { " " } else { "" };
+    Some(cx.token(TokenKind::Function, name) + sep + args)
 }
 
 /// Demangle a symbol name.
 ///
 /// Returns `None` if the input is not a valid mangled name.
-pub fn demangle(mut str: &str, options: &DemangleOptions) -> Option<String> {
-    if !str.is_ascii() {
-        return None;
-    }
+pub fn demangle(str: &str, options: &DemangleOptions) -> Option<String> {
+    demangle_bytes(str.as_bytes(), Encoding::Utf8, options)
+}
 
-    let mut special = false;
-    let mut cnst = false;
-    let mut fn_name: String;
-    let mut return_type_pre = String::new();
-    let mut return_type_post = String::new();
-    let mut qualified = String::new();
-    let mut static_var = String::new();
-
-    // Handle new static function variables (Wii CW)
-    let guard = str.starts_with("@GUARD@");
-    if guard || str.starts_with("@LOCAL@") {
-        str = &str[7..];
-        let idx = str.rfind('@')?;
-        let (rest, var) = str.split_at(idx);
-        if guard {
-            static_var = format!("{} guard", &var[1..]);
-        } else {
-            static_var = var[1..].to_string();
-        }
-        str = rest;
+/// Demangle a symbol name from raw bytes, such as an ELF string table entry.
+///
+/// Length prefixes count bytes, so identifiers may contain any non-ASCII bytes;
+/// `encoding` selects how those are shown in the output.
+///
+/// Returns `None` if the input is not a valid mangled name.
+pub fn demangle_bytes(str: &[u8], encoding: Encoding, options: &DemangleOptions) -> Option<String> {
+    let cx = Context::new(options, encoding);
+    demangle_text(str, cx).map(|text| text.str)
+}
+
+/// Demangle a symbol name, recording which bytes of the mangled name produced
+/// each part of the output.
+///
+/// Returns `None` if the input is not a valid mangled name.
+pub fn demangle_spanned(str: &str, options: &DemangleOptions) -> Option<Spanned> {
+    let str = str.as_bytes();
+    let cx = Context::new(options, Encoding::Utf8).with_spans(str);
+    Some(demangle_text(str, cx)?.into_spanned())
+}
+
+/// Demangle a type on its own, such as `PCc` or `Q23std6string`, as found in
+/// parameter lists.
+///
+/// Returns `None` if the input is not a valid mangled type.
+pub fn demangle_type_name(str: &str, options: &DemangleOptions) -> Option<String> {
+    let cx = Context::new(options, Encoding::Utf8);
+    match demangle_arg(str.as_bytes(), cx)? {
+        (pre, post, []) => Some(pre.str + &post.str),
+        _ => None,
     }
+}
 
-    if str.starts_with("__") {
-        special = true;
-        str = &str[2..];
+/// Demangle a symbol name into a sequence of tokens, for syntax highlighting.
+///
+/// Returns `None` if the input is not a valid mangled name.
+pub fn demangle_tokens(str: &str, options: &DemangleOptions) -> Option<Tokens> {
+    let cx = Context::new(options, Encoding::Utf8).with_tokens();
+    Some(demangle_text(str.as_bytes(), cx)?.into_tokens())
+}
+
+/// Demangle a symbol name, breaking it down into its parts.
+///
+/// Returns `None` if the input is not a valid mangled name.
+pub fn demangle_parts(str: &str, options: &DemangleOptions) -> Option<Parts> {
+    let input = str.as_bytes();
+    let cx = Context::new(options, Encoding::Utf8).with_spans(input);
+    if let Some(local_static) = demangle_local_static(str, options) {
+        let name = match (&local_static.variable, local_static.index) {
+            (Some(variable), _) => variable.clone(),
+            (None, Some(index)) => format!("localstatic{index}"),
+            (None, None) => return None,
+        };
+        return Some(Parts {
+            demangled: demangle_symbol(input, cx)?.str,
+            kind: SymbolKind::LocalStatic,
+            class_path: Vec::new(),
+            name,
+            parameters: None,
+            parameter_types: None,
+            return_type: None,
+            return_type_parts: None,
+            is_const: false,
+            local_static: Some(local_static),
+        });
     }
-    {
-        let mut idx = find_split(str, special, options)?;
-        // Handle any trailing underscores in the function name
-        while str.chars().nth(idx + 2) == Some('_') {
-            idx += 1;
-        }
-        let (fn_name_out, mut rest) = str.split_at(idx);
-        if special {
-            if fn_name_out == "init" {
-                // Special case for double __
-                let rest_idx = rest[2..].find("__")?;
-                fn_name = str[..rest_idx + 6].to_string();
-                rest = &rest[rest_idx + 2..];
-            } else {
-                fn_name = fn_name_out.to_string();
+    if let Some(symbol) = parse_symbol(input, cx) {
+        let parts_at = |range: Range<usize>| type_parts(input.get(range)?, options);
+        let (parameters, parameter_types) = match &symbol.args {
+            Some(args) if symbol.omit_void && args.as_str() == "void" => {
+                (Some(Vec::new()), Some(Vec::new()))
+            }
+            Some(args) => {
+                let ranges = args.outer_span_ranges(SpanKind::Parameter);
+                let types = ranges.into_iter().map(|(_, input)| parts_at(input)).collect();
+                (Some(args.outer_spans(SpanKind::Parameter)), types)
             }
+            None => (None, None),
+        };
+        let (return_type, return_type_parts) = if symbol.return_type_pre.is_empty() {
+            (None, None)
         } else {
-            let (name, args) = demangle_template_args(fn_name_out, options)?;
-            fn_name = format!("{name}{args}");
+            let ranges = symbol.return_type_pre.outer_span_ranges(SpanKind::ReturnType);
+            let parts = ranges.into_iter().next().and_then(|(_, input)| parts_at(input));
+            (Some(symbol.return_type_pre.str.clone() + &symbol.return_type_post.str), parts)
+        };
+        return Some(Parts {
+            kind: symbol.kind,
+            class_path: symbol.qualified.outer_spans(SpanKind::Name),
+            name: symbol.name.str.clone(),
+            parameters,
+            parameter_types,
+            return_type,
+            return_type_parts,
+            is_const: symbol.cnst.is_some(),
+            local_static: None,
+            demangled: symbol.into_text().str,
+        });
+    }
+    let text = demangle_static_init(input, cx)?;
+    let kind = if str.starts_with("__std__") {
+        SymbolKind::StaticDestructor
+    } else {
+        SymbolKind::StaticInitializer
+    };
+    // The file name is the quoted part of the description
+    let name = text.as_str().split('\'').nth(1)?.to_string();
+    Some(Parts {
+        demangled: text.str,
+        kind,
+        class_path: Vec::new(),
+        name,
+        parameters: None,
+        parameter_types: None,
+        return_type: None,
+        return_type_parts: None,
+        is_const: false,
+        local_static: None,
+    })
+}
+
+/// Breaks down a mangled type into its modifiers and the type they apply to.
+fn type_parts(str: &[u8], options: &DemangleOptions) -> Option<TypeParts> {
+    // Modifiers come first in mangled types, outermost first. Function types
+    // can't be demangled on their own, so a pointer to one is left whole
+    let len = str.iter().position(|c| !matches!(c, b'P' | b'R' | b'C' | b'V')).unwrap_or(str.len());
+    let cx = Context::new(options, Encoding::Utf8);
+    (0..=len).rev().find_map(|len| {
+        let (pre, post, rest) = demangle_arg(&str[len..], cx)?;
+        if !rest.is_empty() {
+            return None;
         }
+        let modifiers = str[..len]
+            .iter()
+            .map(|c| match c {
+                b'P' => TypeModifier::Pointer,
+                b'R' => TypeModifier::Reference,
+                b'C' => TypeModifier::Const,
+                _ => TypeModifier::Volatile,
+            })
+            .collect();
+        Some(TypeParts { modifiers, base: pre.str + &post.str })
+    })
+}
 
-        // Handle old static function variables (GC CW)
-        if let Some(first_idx) = fn_name.find('$') {
-            let second_idx = fn_name[first_idx + 1..].find('$')?;
-            let (var, rest) = fn_name.split_at(first_idx);
-            let (var_type, rest) = rest[1..].split_at(second_idx);
-            if !var_type.starts_with("localstatic") {
-                return None;
+/// Demangle a symbol name, recording each step taken to parse it.
+///
+/// The steps are returned even if the symbol fails to demangle, showing how far
+/// parsing got.
+pub fn explain(str: &str, options: &DemangleOptions) -> Explanation {
+    let input = str.as_bytes();
+    let trace = RefCell::new(Trace::new(input));
+    let cx = Context::new(options, Encoding::Utf8).with_trace(&trace);
+    let demangled = demangle_text(input, cx).map(|text| text.str);
+    Explanation { steps: trace.into_inner().into_steps(), demangled }
+}
+
+fn demangle_text(str: &[u8], cx: Context) -> Option<Text> {
+    demangle_symbol(str, cx).or_else(|| demangle_static_init(str, cx))
+}
+
+/// A function-local static variable or its guard variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalStatic {
+    /// The demangled enclosing function.
+    pub function: String,
+    /// The variable's name. GC CW guard variables don't record it.
+    pub variable: Option<String>,
+    /// The `$localstaticN` index. Only GC CW records it.
+    pub index: Option<u32>,
+    /// Whether this is the guard variable for the static's initialization.
+    pub is_guard: bool,
+}
+
+/// Demangle a function-local static variable or guard variable into its parts.
+///
+/// Returns `None` if the input is not a valid mangled local static.
+pub fn demangle_local_static(str: &str, options: &DemangleOptions) -> Option<LocalStatic> {
+    let cx = Context::new(options, Encoding::Utf8);
+    let (function, local) = demangle_local_name(str.as_bytes(), cx)?;
+    Some(LocalStatic {
+        function: function.str,
+        variable: local.name.map(|name| cx.ident(name)),
+        index: local.index,
+        is_guard: local.guard,
+    })
+}
+
+fn demangle_symbol(str: &[u8], cx: Context) -> Option<Text> {
+    if let Some((function, local)) = demangle_local_name(str, cx) {
+        let mut name = match (local.name, local.index) {
+            (Some(name), _) => {
+                let mut text = cx.token(TokenKind::Variable, &cx.ident(name));
+                cx.mark(&mut text, SpanKind::Name, name, &name[name.len()..]);
+                text
             }
-            if var == "init" {
-                // Sadly, $localstatic doesn't provide the variable name in guard/init
-                static_var = format!("{var_type} guard");
+            (None, Some(index)) => cx.token(TokenKind::Variable, &format!("localstatic{index}")),
+            (None, None) => return None,
+        };
+        if local.guard {
+            name = name + " " + cx.token(TokenKind::Annotation, "guard");
+        }
+        return Some(function + "::" + name);
+    }
+    let step = cx.step(StepKind::Symbol, str);
+    let text = parse_symbol(str, cx)?.into_text();
+    step.end(&[], &[text.as_str()]);
+    Some(text)
+}
+
+/// A function or variable symbol, demangled but not yet put together.
+struct Symbol {
+    kind: SymbolKind,
+    /// The qualified class name, if any.
+    qualified: Text,
+    /// The unqualified name with its template arguments.
+    name: Text,
+    /// The parameters, without parentheses, if this is a function.
+    args: Option<Text>,
+    cnst: Option<Text>,
+    return_type_pre: Text,
+    return_type_post: Text,
+    omit_void: bool,
+}
+
+impl Symbol {
+    fn into_text(self) -> Text {
+        let mut text = self.name;
+        if let Some(args) = self.args {
+            if self.omit_void && args.as_str() == "void" {
+                text += "()";
             } else {
-                static_var = var.to_string();
+                text = text + "(" + args + ")";
             }
-            fn_name = rest[1..].to_string();
         }
+        if let Some(cnst) = self.cnst {
+            text = text + " " + cnst;
+        }
+        if !self.qualified.is_empty() {
+            text = self.qualified + "::" + text;
+        }
+        if !self.return_type_pre.is_empty() {
+            text = self.return_type_pre + " " + text + self.return_type_post;
+        }
+        text
+    }
+}
 
-        str = &rest[2..];
+/// Parses a function or variable symbol that isn't function-local.
+fn parse_symbol(mut str: &[u8], cx: Context) -> Option<Symbol> {
+    if str.starts_with(b"@LOCAL@") || str.starts_with(b"@GUARD@") {
+        return None;
     }
-    let mut class_name = String::new();
-    if !str.starts_with('F') {
-        let (name, qualified_name, rest) = demangle_qualified_name(str, options)?;
-        class_name = name;
-        qualified = qualified_name;
+
+    let start = str;
+    let mut special = false;
+    let mut cnst = None;
+    let mut args = None;
+    let kind;
+    let mut fn_name: Text;
+    let mut return_type_pre = Text::new();
+    let mut return_type_post = Text::new();
+    let mut qualified = Text::new();
+
+    if let Some(rest) = str.strip_prefix(b"__") {
+        special = true;
         str = rest;
     }
-    if special {
-        fn_name = demangle_special_function(fn_name.as_str(), class_name.as_str(), options)?;
-    }
-    if str.starts_with('C') {
-        str = &str[1..];
-        cnst = true;
-    }
-    if str.starts_with('F') {
-        str = &str[1..];
-        let (args, rest) = demangle_function_args(str, options)?;
-        if options.omit_empty_parameters && args == "void" {
-            fn_name = format!("{fn_name}()");
+    {
+        let name_step = cx.step(StepKind::FunctionName, start);
+        let (mut fn_name_out, mut tmpl_args, mut rest) = demangle_function_name(str, special, cx)?;
+        if special && fn_name_out == b"init" && tmpl_args.is_empty() {
+            // `__init__` is followed by the name of the variable it initializes,
+            // which may have template arguments of its own
+            let (name, name_args, name_rest) = demangle_function_name(rest, false, cx)?;
+            fn_name_out = &str[..str.len() - rest.len() + name.len()];
+            tmpl_args = name_args;
+            rest = name_rest;
+        }
+
+        // `$` only appears in local names, which are handled above
+        if fn_name_out.contains(&b'$') {
+            return None;
+        }
+        // The name ends just before the `__` separator
+        let name_end = &start[start.len() - rest.len() - 2..];
+        let raw_name = core::str::from_utf8(fn_name_out).unwrap_or_default();
+        name_step.end(name_end, &[raw_name, tmpl_args.as_str()]);
+        cx.step(StepKind::Separator, name_end).end(rest, &[]);
+        str = rest;
+
+        let mut class_name = String::new();
+        if !str.starts_with(b"F") {
+            let (name, qualified_name, rest) = demangle_qualified_name(str, cx)?;
+            class_name = name;
+            qualified = qualified_name;
+            str = rest;
+        }
+        let is_function = str.starts_with(b"F") || str.starts_with(b"CF");
+        if special {
+            fn_name = demangle_special_function(fn_name_out, tmpl_args, &class_name, cx)?;
+            cx.step(StepKind::SpecialName, start).end(name_end, &[fn_name.as_str()]);
+            kind = match fn_name_out {
+                b"ct" => SymbolKind::Constructor,
+                b"dt" => SymbolKind::Destructor,
+                _ if fn_name_out.starts_with(b"op")
+                    && fn_name.as_str().starts_with("operator ") =>
+                {
+                    SymbolKind::Conversion
+                }
+                _ if fn_name.as_str().starts_with("operator") => SymbolKind::Operator,
+                _ => SymbolKind::Special,
+            };
         } else {
-            fn_name = format!("{fn_name}({args})");
+            let token_kind = if is_function { TokenKind::Function } else { TokenKind::Variable };
+            fn_name = Text::token(token_kind, &cx.ident(fn_name_out)) + tmpl_args;
+            kind = if is_function { SymbolKind::Function } else { SymbolKind::Variable };
         }
+        cx.mark(&mut fn_name, SpanKind::Name, start, name_end);
+    }
+    if let Some(rest) = str.strip_prefix(b"C") {
+        let mut text = cx.token(TokenKind::Keyword, "const");
+        cx.mark(&mut text, SpanKind::Qualifier, str, rest);
+        cx.step(StepKind::Const, str).end(rest, &[text.as_str()]);
+        cnst = Some(text);
         str = rest;
     }
-    if str.starts_with('_') {
-        str = &str[1..];
-        let (ret_pre, ret_post, rest) = demangle_arg(str, options)?;
+    if let Some(rest) = str.strip_prefix(b"F") {
+        let step = cx.step(StepKind::Parameters, str);
+        let (fn_args, rest) = demangle_function_args(rest, cx)?;
+        step.end(rest, &["(", fn_args.as_str(), ")"]);
+        args = Some(fn_args);
+        str = rest;
+    }
+    if let Some(rest) = str.strip_prefix(b"_") {
+        let step = cx.step(StepKind::ReturnType, str);
+        let (mut ret_pre, mut ret_post, ret_rest) = demangle_arg(rest, cx)?;
+        cx.mark(&mut ret_pre, SpanKind::ReturnType, rest, ret_rest);
+        cx.mark(&mut ret_post, SpanKind::ReturnType, rest, ret_rest);
+        step.end(ret_rest, &[ret_pre.as_str(), ret_post.as_str()]);
         return_type_pre = ret_pre;
         return_type_post = ret_post;
-        str = rest;
+        str = ret_rest;
     }
     if !str.is_empty() {
         return None;
     }
-    if cnst {
-        fn_name = format!("{fn_name} const");
-    }
-    if !qualified.is_empty() {
-        fn_name = format!("{qualified}::{fn_name}");
+    Some(Symbol {
+        kind,
+        qualified,
+        name: fn_name,
+        args,
+        cnst,
+        return_type_pre,
+        return_type_post,
+        omit_void: cx.options.omit_empty_parameters,
+    })
+}
+
+/// A function-local name, split from its enclosing function's symbol.
+struct LocalName<'a> {
+    function: &'a [u8],
+    name: Option<&'a [u8]>,
+    index: Option<u32>,
+    guard: bool,
+}
+
+/// Splits a function-local name into the enclosing function's symbol and the
+/// local name.
+///
+/// Wii CW encodes these as `@LOCAL@function@name`, or `@GUARD@function@name` for
+/// guard variables. GC CW uses `name$localstaticN$function`, with `init` as the
+/// name of the guard variable, which therefore only records the index.
+fn split_local_name(str: &[u8]) -> Option<LocalName<'_>> {
+    let guard = str.starts_with(b"@GUARD@");
+    if guard || str.starts_with(b"@LOCAL@") {
+        let str = &str[7..];
+        let idx = str.iter().rposition(|&c| c == b'@')?;
+        let (function, name) = (&str[..idx], &str[idx + 1..]);
+        if function.is_empty() || name.is_empty() {
+            return None;
+        }
+        return Some(LocalName { function, name: Some(name), index: None, guard });
     }
-    if !return_type_pre.is_empty() {
-        fn_name = format!("{return_type_pre} {fn_name}{return_type_post}");
+    let idx = str.iter().position(|&c| c == b'$')?;
+    let (name, rest) = (&str[..idx], &str[idx + 1..]);
+    if name.is_empty() || find_subslice(name, b"__").is_some() {
+        return None;
     }
-    if !static_var.is_empty() {
-        fn_name = format!("{fn_name}::{static_var}");
+    let rest = rest.strip_prefix(b"localstatic")?;
+    let (index, rest) = parse_digits(rest)?;
+    let function = rest.strip_prefix(b"$").filter(|f| !f.is_empty())?;
+    let index = Some(u32::try_from(index).ok()?);
+    if name == b"init" {
+        Some(LocalName { function, name: None, index, guard: true })
+    } else {
+        Some(LocalName { function, name: Some(name), index, guard: false })
     }
-    Some(fn_name)
 }
 
-/// Finds the first double underscore in the string, excluding any that are part of a
-/// template argument list or operator name.
-fn find_split(s: &str, special: bool, options: &DemangleOptions) -> Option<usize> {
-    let mut start = 0;
-    if special && s.starts_with("op") {
-        let (_, _, rest) = demangle_arg(&s[2..], options)?;
-        start = s.len() - rest.len();
+/// Demangles the enclosing function of a function-local name.
+fn demangle_local_name<'a>(str: &'a [u8], cx: Context) -> Option<(Text, LocalName<'a>)> {
+    let local = split_local_name(str)?;
+    let step = cx.step(StepKind::LocalName, str);
+    // The enclosing function may itself be local, so it is demangled in full
+    let function = demangle_symbol(local.function, cx.nested()?)?;
+    step.end(&[], &[function.as_str()]);
+    Some((function, local))
+}
+
+/// Source file extensions recognized in static initializer names.
+const SOURCE_EXTENSIONS: &[&str] = &["c", "cc", "cp", "cpp", "cxx"];
+
+/// Demangles the per-file static initializer and destructor functions, such as
+/// `__sinit_CGameState_cpp`, or the cfront-style `__sti__main_cp_` and `__std__main_cp_`.
+fn demangle_static_init(str: &[u8], cx: Context) -> Option<Text> {
+    let (kind, file) = if let Some(file) = str.strip_prefix(b"__sinit_") {
+        ("initializer", file)
+    } else if let Some(file) = str.strip_prefix(b"__sti__") {
+        ("initializer", file)
+    } else if let Some(file) = str.strip_prefix(b"__std__") {
+        ("destructor", file)
+    } else {
+        return None;
+    };
+    // cfront-style names may end with an underscore
+    let file = file.strip_suffix(b"_").unwrap_or(file);
+    if file.is_empty() {
+        return None;
     }
-    let mut depth = 0;
-    let bytes = s.as_bytes();
-    for i in start..s.len() {
-        match bytes[i] {
-            b'<' => depth += 1,
-            b'>' => depth -= 1,
-            b'_' => {
-                if bytes.get(i + 1).cloned() == Some(b'_') && depth == 0 {
-                    return Some(i);
-                }
+    // The `.` before the extension is replaced with `_`; other `_` are ambiguous
+    let mut file = cx.ident(file);
+    if let Some(idx) = file.rfind('_') {
+        let ext = &file[idx + 1..];
+        if SOURCE_EXTENSIONS.iter().any(|e| e.eq_ignore_ascii_case(ext)) {
+            file.replace_range(idx..idx + 1, ".");
+        }
+    }
+    let description = cx.token(TokenKind::Annotation, &format!("static {kind} for"));
+    let text = description + " '" + cx.token(TokenKind::Literal, &file) + "'";
+    cx.step(StepKind::StaticInitializer, str).end(&[], &[text.as_str()]);
+    Some(text)
+}
+
+/// Finds the first occurrence of `needle` in `haystack`.
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Parses the function or variable name at the start of a symbol, up to the `__`
+/// separating it from the class name and signature.
+///
+/// Returns the raw name, its demangled template arguments and the remainder after
+/// the separator.
+fn demangle_function_name<'a>(
+    str: &'a [u8],
+    special: bool,
+    cx: Context,
+) -> Option<(&'a [u8], Text, &'a [u8])> {
+    let mut end = 0;
+    if special && str.starts_with(b"op") {
+        // Conversion operator, whose target type may itself contain `__` or `<`
+        if let Some((_, _, rest)) = demangle_arg(&str[2..], cx) {
+            if rest.starts_with(b"<") || rest.starts_with(b"__") {
+                end = str.len() - rest.len();
             }
-            _ => {}
         }
     }
-    None
+    let start = end;
+    while end < str.len() && str[end] != b'<' && !str[end..].starts_with(b"__") {
+        end += 1;
+    }
+    // A `>` outside of template arguments is unbalanced
+    if str[start..end].contains(&b'>') {
+        return None;
+    }
+    let mut rest = &str[end..];
+    let mut tmpl_args = Text::new();
+    if rest.starts_with(b"<") {
+        let (args, args_rest) = demangle_template_arg_list(rest, cx)?;
+        tmpl_args = args;
+        rest = args_rest;
+    } else {
+        // Handle any trailing underscores in the function name
+        while rest.starts_with(b"___") {
+            end += 1;
+            rest = &rest[1..];
+        }
+    }
+    let rest = rest.strip_prefix(b"__")?;
+    Some((&str[..end], tmpl_args, rest))
 }
 
 #[cfg(test)]
 mod tests {
+    use alloc::{vec, vec::Vec};
+
     use super::*;
 
     #[test]
     fn test_demangle_template_args() {
         let options = DemangleOptions::default();
+        let cx = Context::new(&options, Encoding::Utf8);
+        let template_args =
+            |s: &'static [u8]| demangle_template_args(s, cx).map(|(name, args)| (name, args.str));
         assert_eq!(
-            demangle_template_args("single_ptr<10CModelData>", &options),
-            Some(("single_ptr", "<CModelData>".to_string()))
+            template_args(b"single_ptr<10CModelData>"),
+            Some((&b"single_ptr"[..], "<CModelData>".into()))
         );
         assert_eq!(
-            demangle_template_args(
-                "basic_string<w,Q24rstl14char_traits<w>,Q24rstl17rmemory_allocator>",
-                &options
-            ),
+            template_args(b"basic_string<w,Q24rstl14char_traits<w>,Q24rstl17rmemory_allocator>"),
             Some((
-                "basic_string",
-                "<wchar_t, rstl::char_traits<wchar_t>, rstl::rmemory_allocator>".to_string()
+                &b"basic_string"[..],
+                "<wchar_t, rstl::char_traits<wchar_t>, rstl::rmemory_allocator>".into()
             ))
         );
     }
@@ -519,20 +1114,20 @@ mod tests {
     #[test]
     fn test_demangle_name() {
         let options = DemangleOptions::default();
+        let cx = Context::new(&options, Encoding::Utf8);
+        let name = |s: &'static [u8]| {
+            demangle_name(s, TokenKind::Class, cx).map(|(name, full, rest)| (name, full.str, rest))
+        };
         assert_eq!(
-            demangle_name("24single_ptr<10CModelData>", &options),
-            Some(("single_ptr".to_string(), "single_ptr<CModelData>".to_string(), ""))
+            name(b"24single_ptr<10CModelData>"),
+            Some(("single_ptr".into(), "single_ptr<CModelData>".into(), &b""[..]))
         );
         assert_eq!(
-            demangle_name(
-                "66basic_string<w,Q24rstl14char_traits<w>,Q24rstl17rmemory_allocator>",
-                &options
-            ),
+            name(b"66basic_string<w,Q24rstl14char_traits<w>,Q24rstl17rmemory_allocator>"),
             Some((
-                "basic_string".to_string(),
-                "basic_string<wchar_t, rstl::char_traits<wchar_t>, rstl::rmemory_allocator>"
-                    .to_string(),
-                ""
+                "basic_string".into(),
+                "basic_string<wchar_t, rstl::char_traits<wchar_t>, rstl::rmemory_allocator>".into(),
+                &b""[..]
             ))
         );
     }
@@ -540,24 +1135,24 @@ mod tests {
     #[test]
     fn test_demangle_qualified_name() {
         let options = DemangleOptions::default();
+        let cx = Context::new(&options, Encoding::Utf8);
+        let qualified_name = |s: &'static [u8]| {
+            demangle_qualified_name(s, cx).map(|(name, full, rest)| (name, full.str, rest))
+        };
+        assert_eq!(qualified_name(b"6CActor"), Some(("CActor".into(), "CActor".into(), &b""[..])));
         assert_eq!(
-            demangle_qualified_name("6CActor", &options),
-            Some(("CActor".to_string(), "CActor".to_string(), ""))
-        );
-        assert_eq!(
-            demangle_qualified_name("Q29CVector3f4EDim", &options),
-            Some(("EDim".to_string(), "CVector3f::EDim".to_string(), ""))
+            qualified_name(b"Q29CVector3f4EDim"),
+            Some(("EDim".into(), "CVector3f::EDim".into(), &b""[..]))
         );
         assert_eq!(
-            demangle_qualified_name(
-                "Q24rstl66basic_string<w,Q24rstl14char_traits<w>,Q24rstl17rmemory_allocator>",
-                &options
+            qualified_name(
+                b"Q24rstl66basic_string<w,Q24rstl14char_traits<w>,Q24rstl17rmemory_allocator>"
             ),
             Some((
-                "basic_string".to_string(),
+                "basic_string".into(),
                 "rstl::basic_string<wchar_t, rstl::char_traits<wchar_t>, rstl::rmemory_allocator>"
-                    .to_string(),
-                ""
+                    .into(),
+                &b""[..]
             ))
         )
     }
@@ -565,26 +1160,48 @@ mod tests {
     #[test]
     fn test_demangle_arg() {
         let options = DemangleOptions::default();
-        assert_eq!(demangle_arg("v", &options), Some(("void".to_string(), "".to_string(), "")));
-        assert_eq!(demangle_arg("b", &options), Some(("bool".to_string(), "".to_string(), "")));
+        let cx = Context::new(&options, Encoding::Utf8);
+        let arg = |s: &'static [u8]| {
+            demangle_arg(s, cx).map(|(pre, post, rest)| (pre.str, post.str, rest))
+        };
+        assert_eq!(arg(b"v"), Some(("void".into(), "".into(), &b""[..])));
+        assert_eq!(arg(b"b"), Some(("bool".into(), "".into(), &b""[..])));
         assert_eq!(
-            demangle_arg("RC9CVector3fUc", &options),
-            Some(("const CVector3f&".to_string(), "".to_string(), "Uc"))
+            arg(b"RC9CVector3fUc"),
+            Some(("const CVector3f&".into(), "".into(), &b"Uc"[..]))
         );
         assert_eq!(
-            demangle_arg("Q24rstl14char_traits<w>,", &options),
-            Some(("rstl::char_traits<wchar_t>".to_string(), "".to_string(), ","))
+            arg(b"Q24rstl14char_traits<w>,"),
+            Some(("rstl::char_traits<wchar_t>".into(), "".into(), &b","[..]))
         );
         assert_eq!(
-            demangle_arg("PFPCcPCc_v", &options),
-            Some(("void (*".to_string(), ")(const char*, const char*)".to_string(), ""))
+            arg(b"PFPCcPCc_v"),
+            Some(("void (*".into(), ")(const char*, const char*)".into(), &b""[..]))
         );
+        assert_eq!(arg(b"r"), Some(("long double".into(), "".into(), &b""[..])));
+        for (code, name) in [
+            ("Sc", "signed char"),
+            ("Uc", "unsigned char"),
+            ("Us", "unsigned short"),
+            ("Ui", "unsigned int"),
+            ("Ul", "unsigned long"),
+            ("Ux", "unsigned long long"),
+            ("Sx", "signed long long"),
+            ("PCSc", "const signed char*"),
+            ("RCUx", "const unsigned long long&"),
+        ] {
+            assert_eq!(arg(code.as_bytes()), Some((name.into(), "".into(), &b""[..])));
+        }
+        // There's no such type as `unsigned wchar_t` or `signed float`
+        for code in ["Uw", "Sw", "Ub", "Uf", "Sd", "Ur", "Uv", "PCUw"] {
+            assert_eq!(arg(code.as_bytes()), None, "{code}");
+        }
         assert_eq!(
-            demangle_arg("RCPCVPCVUi", &options),
+            arg(b"RCPCVPCVUi"),
             Some((
-                "const volatile unsigned int* const volatile* const&".to_string(),
-                "".to_string(),
-                ""
+                "const volatile unsigned int* const volatile* const&".into(),
+                "".into(),
+                &b""[..]
             ))
         );
     }
@@ -592,11 +1209,14 @@ mod tests {
     #[test]
     fn test_demangle_function_args() {
         let options = DemangleOptions::default();
-        assert_eq!(demangle_function_args("v", &options), Some(("void".to_string(), "")));
-        assert_eq!(demangle_function_args("b", &options), Some(("bool".to_string(), "")));
+        let cx = Context::new(&options, Encoding::Utf8);
+        let args =
+            |s: &'static [u8]| demangle_function_args(s, cx).map(|(args, rest)| (args.str, rest));
+        assert_eq!(args(b"v"), Some(("void".into(), &b""[..])));
+        assert_eq!(args(b"b"), Some(("bool".into(), &b""[..])));
         assert_eq!(
-            demangle_function_args("RC9CVector3fUc_x", &options),
-            Some(("const CVector3f&, unsigned char".to_string(), "_x"))
+            args(b"RC9CVector3fUc_x"),
+            Some(("const CVector3f&, unsigned char".into(), &b"_x"[..]))
         );
     }
 
@@ -719,6 +1339,14 @@ mod tests {
             demangle("__init__mNull__Q24rstl66basic_string<c,Q24rstl14char_traits<c>,Q24rstl17rmemory_allocator>", &options),
             Some("rstl::basic_string<char, rstl::char_traits<char>, rstl::rmemory_allocator>::__init__mNull".to_string())
         );
+        assert_eq!(
+            demangle("__init__sInstance<i>__4CFoo", &options),
+            Some("CFoo::__init__sInstance<int>".to_string())
+        );
+        assert_eq!(
+            demangle("__init__sInstance<Q23std3__y>__4CFoo", &options),
+            Some("CFoo::__init__sInstance<std::__y>".to_string())
+        );
         assert_eq!(
             demangle("__dt__26__partial_array_destructorFv", &options),
             Some("__partial_array_destructor::~__partial_array_destructor()".to_string())
@@ -755,11 +1383,62 @@ mod tests {
                 "nw4r::g3d::GetAnmPlayPolicy(nw4r::g3d::AnmPolicy)::policyTable guard".to_string()
             )
         );
+        assert_eq!(
+            demangle("__sinit_CGameState_cpp", &options),
+            Some("static initializer for 'CGameState.cpp'".to_string())
+        );
+        assert_eq!(
+            demangle("__sinit_d_a_alink_cpp", &options),
+            Some("static initializer for 'd_a_alink.cpp'".to_string())
+        );
+        assert_eq!(
+            demangle("__sinit_JASAudioThread_cpp", &options),
+            Some("static initializer for 'JASAudioThread.cpp'".to_string())
+        );
+        assert_eq!(
+            demangle("__sinit_ansi_files_c", &options),
+            Some("static initializer for 'ansi_files.c'".to_string())
+        );
+        // No GameCube or Wii titles are known to use the cfront-style names,
+        // so these only check the format
+        assert_eq!(
+            demangle("__sti__main_cp_", &options),
+            Some("static initializer for 'main.cp'".to_string())
+        );
+        assert_eq!(
+            demangle("__std__main_cp_", &options),
+            Some("static destructor for 'main.cp'".to_string())
+        );
+        assert_eq!(
+            demangle("__sinit_unknown_ext", &options),
+            Some("static initializer for 'unknown_ext'".to_string())
+        );
+        assert_eq!(demangle("__sinit_", &options), None);
+        // Unbalanced template brackets
+        assert_eq!(demangle("int>__Fv", &options), None);
+        assert_eq!(demangle("x>__3FooFv", &options), None);
+        assert_eq!(demangle("a<b__Fv", &options), None);
+        assert_eq!(demangle("a<i>>__Fv", &options), None);
         // Truncated symbol
         assert_eq!(
             demangle("lower_bound<Q24rstl180const_pointer_iterator<Q24rstl33pair<Ui,22CAdditiveAnimationInfo>,Q24rstl77vector<Q24rstl33pair<Ui,22CAdditiveAnimationInfo>,Q24rstl17rmemory_allocator>,Q24rstl17rmemory_allocator>,Ui,Q24rstl79pair_sorter_finder<Q24rstl33pair<Ui,22CAdditiveAnimationInfo>,Q24rstl8less<Ui>>>__4rstlFQ24rstl180const_pointer_iterator<Q24rstl33pair<Ui,22CAdditiveAnimationInfo>,Q24rstl77vector<Q24rstl33pair<Ui,22CAdditiveAnimationInfo>,Q24rstl17rmemory_allocator>,Q24rstl17rmemory_allocator>Q24rstl180const_p", &options),
             None
         );
+        assert_eq!(
+            demangle("abs__3stdFRCQ23std10complex<r>", &options),
+            Some("std::abs(const std::complex<long double>&)".to_string())
+        );
+        assert_eq!(
+            demangle("__ct__Q23std10complex<r>FRCrRCr", &options),
+            Some(
+                "std::complex<long double>::complex(const long double&, const long double&)"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            demangle("Convert__FScUcSsUsSiUiSlUlSxUx", &options),
+            Some("Convert(signed char, unsigned char, signed short, unsigned short, signed int, unsigned int, signed long, unsigned long, signed long long, unsigned long long)".to_string())
+        );
         assert_eq!(
             demangle("test__FRCPCPCi", &options),
             Some("test(const int* const* const&)".to_string()),
@@ -793,6 +1472,598 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_demangle_local_names() {
+        let options = DemangleOptions::default();
+        let local = |s| demangle(s, &options);
+        assert_eq!(
+            local("method__32@LOCAL@func__5OuterFi@LocalClassFv"),
+            Some("Outer::func(int)::LocalClass::method()".to_string())
+        );
+        assert_eq!(
+            local("__ct__32@LOCAL@func__5OuterFi@LocalClassFv"),
+            Some("Outer::func(int)::LocalClass::LocalClass()".to_string())
+        );
+        assert_eq!(
+            local("__cl__26@LOCAL@Run__FPv@Visitor<i>CFi_b"),
+            Some("bool Run(void*)::Visitor<int>::operator()(int) const".to_string())
+        );
+        assert_eq!(
+            local("Next__Q233Local$localstatic1$func__5OuterFi4NodeFv"),
+            Some("Outer::func(int)::Local::Node::Next()".to_string())
+        );
+        // Statics inside member functions of local classes
+        assert_eq!(
+            local("@LOCAL@method__32@LOCAL@func__5OuterFi@LocalClassFv@sCount"),
+            Some("Outer::func(int)::LocalClass::method()::sCount".to_string())
+        );
+        assert_eq!(
+            local("@GUARD@method__32@LOCAL@func__5OuterFi@LocalClassFv@sCount"),
+            Some("Outer::func(int)::LocalClass::method()::sCount guard".to_string())
+        );
+        assert_eq!(
+            local("sCount$localstatic0$method__33Local$localstatic1$func__5OuterFiFv"),
+            Some("Outer::func(int)::Local::method()::sCount".to_string())
+        );
+        assert_eq!(
+            local("init$localstatic2$method__33Local$localstatic1$func__5OuterFiFv"),
+            Some("Outer::func(int)::Local::method()::localstatic2 guard".to_string())
+        );
+        // Classes nested in member functions of local classes
+        assert_eq!(
+            local("Get__57@LOCAL@method__32@LOCAL@func__5OuterFi@LocalClassFv@InnerFv"),
+            Some("Outer::func(int)::LocalClass::method()::Inner::Get()".to_string())
+        );
+        // Nested local scopes without a mangled class
+        assert_eq!(
+            local("@LOCAL@@LOCAL@func__Fv@scope@value"),
+            Some("func()::scope::value".to_string())
+        );
+        assert_eq!(local("@LOCAL@func__Fv"), None);
+        assert_eq!(local("@LOCAL@func__Fv@"), None);
+        assert_eq!(local("value$localstatic$func__Fv"), None);
+        assert_eq!(local("value$static0$func__Fv"), None);
+        assert_eq!(local("method__30@GUARD@func__Fv@LocalClassFv"), None);
+    }
+
+    #[test]
+    fn test_demangle_type_name() {
+        let options = DemangleOptions::default();
+        let ty = |str| demangle_type_name(str, &options);
+        assert_eq!(ty("i"), Some("int".to_string()));
+        assert_eq!(ty("PCc"), Some("const char*".to_string()));
+        assert_eq!(ty("Q23std6string"), Some("std::string".to_string()));
+        assert_eq!(ty("PFPCc_v"), Some("void (*)(const char*)".to_string()));
+        assert_eq!(ty("ii"), None);
+        assert_eq!(ty("Update__6CActorFv"), None);
+    }
+
+    #[test]
+    fn test_demangle_parts() {
+        let options = DemangleOptions::default();
+        let parts = |str| demangle_parts(str, &options);
+        let strings = |strs: &[&str]| strs.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            parts("Get__Q23std9Bar<s,-1>CFPA3_PCiPFPCc_v"),
+            Some(Parts {
+                demangled:
+                    "std::Bar<short, -1>::Get(const int*(*)[3], void (*)(const char*)) const"
+                        .to_string(),
+                kind: SymbolKind::Function,
+                class_path: strings(&["std", "Bar<short, -1>"]),
+                name: "Get".to_string(),
+                parameters: Some(strings(&["const int*(*)[3]", "void (*)(const char*)"])),
+                // Pointers to functions are left whole
+                parameter_types: Some(vec![
+                    TypeParts {
+                        modifiers: vec![TypeModifier::Pointer],
+                        base: "const int*[3]".to_string(),
+                    },
+                    TypeParts { modifiers: vec![], base: "void (*)(const char*)".to_string() },
+                ]),
+                return_type: None,
+                return_type_parts: None,
+                is_const: true,
+                local_static: None,
+            })
+        );
+        let const_ref = TypeParts {
+            modifiers: vec![TypeModifier::Reference, TypeModifier::Const],
+            base: "int".to_string(),
+        };
+        assert_eq!(
+            parts("IsEqual<i>__FRCiRCi_b").map(|p| (p.parameter_types, p.return_type_parts)),
+            Some((
+                Some(vec![const_ref.clone(), const_ref]),
+                Some(TypeParts { modifiers: vec![], base: "bool".to_string() })
+            ))
+        );
+        assert_eq!(
+            parts("IsEqual<i>__FRCiRCi_b").map(|p| (p.name, p.parameters, p.return_type)),
+            Some((
+                "IsEqual<int>".to_string(),
+                Some(strings(&["const int&", "const int&"])),
+                Some("bool".to_string())
+            ))
+        );
+        let kind = |str| parts(str).map(|p| (p.kind, p.name, p.parameters));
+        assert_eq!(
+            kind("__ct__6CActorFv"),
+            Some((SymbolKind::Constructor, "CActor".to_string(), Some(vec![])))
+        );
+        assert_eq!(
+            kind("__dt__6CActorFv"),
+            Some((SymbolKind::Destructor, "~CActor".to_string(), Some(vec![])))
+        );
+        assert_eq!(
+            kind("__nw__FUl"),
+            Some((
+                SymbolKind::Operator,
+                "operator new".to_string(),
+                Some(strings(&["unsigned long"]))
+            ))
+        );
+        assert_eq!(
+            kind("__opb__6CActorCFv"),
+            Some((SymbolKind::Conversion, "operator bool".to_string(), Some(vec![])))
+        );
+        assert_eq!(
+            kind("__vt__6CActor"),
+            Some((SymbolKind::Special, "__vtable".to_string(), None))
+        );
+        assert_eq!(
+            kind("mInstance__6CActor"),
+            Some((SymbolKind::Variable, "mInstance".to_string(), None))
+        );
+        assert_eq!(
+            kind("__sinit_CGameState_cpp"),
+            Some((SymbolKind::StaticInitializer, "CGameState.cpp".to_string(), None))
+        );
+        assert_eq!(
+            kind("__std__main_cp_"),
+            Some((SymbolKind::StaticDestructor, "main.cp".to_string(), None))
+        );
+        // Classes local to a function are a single component
+        assert_eq!(
+            parts("method__32@LOCAL@func__5OuterFi@LocalClassFv").map(|p| p.class_path),
+            Some(strings(&["Outer::func(int)::LocalClass"]))
+        );
+        let local = parts("@GUARD@GetAnmPlayPolicy__Q24nw4r3g3dFQ34nw4r3g3d9AnmPolicy@policyTable")
+            .unwrap();
+        assert_eq!(local.kind, SymbolKind::LocalStatic);
+        assert_eq!(local.name, "policyTable");
+        assert_eq!(
+            local.local_static.map(|l| (l.function, l.is_guard)),
+            Some(("nw4r::g3d::GetAnmPlayPolicy(nw4r::g3d::AnmPolicy)".to_string(), true))
+        );
+        assert_eq!(parts("Update__6CActorFz"), None);
+    }
+
+    #[test]
+    fn test_explain() {
+        use StepKind::*;
+        let options = DemangleOptions::default();
+        let str = "Update__6CActorCFPCc_v";
+        let explanation = explain(str, &options);
+        let steps = explanation
+            .steps
+            .iter()
+            .map(|step| (step.depth, step.kind, &str[step.input.clone()], step.output.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(steps, vec![
+            (0, Symbol, str, Some("void CActor::Update(const char*) const")),
+            (1, FunctionName, "Update", Some("Update")),
+            (1, Separator, "__", Some("")),
+            (1, Name, "6CActor", Some("CActor")),
+            (1, Const, "C", Some("const")),
+            (1, Parameters, "FPCc", Some("(const char*)")),
+            (2, Type, "PCc", Some("const char*")),
+            (3, Qualifiers, "PC", Some("const *")),
+            (1, ReturnType, "_v", Some("void")),
+            (2, Type, "v", Some("void")),
+        ]);
+        assert_eq!(
+            explanation.demangled.as_deref(),
+            Some("void CActor::Update(const char*) const")
+        );
+
+        // Failed steps cover the rest of the input
+        let str = "Update__Q26CActorFv";
+        let explanation = explain(str, &options);
+        let steps = explanation
+            .steps
+            .iter()
+            .map(|step| (step.depth, step.kind, &str[step.input.clone()], step.output.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(steps, vec![
+            (0, Symbol, str, None),
+            (1, FunctionName, "Update", Some("Update")),
+            (1, Separator, "__", Some("")),
+            (1, QualifiedName, "Q26CActorFv", None),
+            (2, Name, "6CActor", Some("CActor")),
+            (2, Name, "Fv", None),
+        ]);
+        assert_eq!(explanation.demangled, None);
+
+        let explanation = explain("__sinit_CGameState_cpp", &options);
+        assert_eq!(
+            explanation.steps.last().map(|step| (step.kind, step.output.as_deref())),
+            Some((StaticInitializer, Some("static initializer for 'CGameState.cpp'")))
+        );
+    }
+
+    #[test]
+    fn test_demangle_local_static() {
+        let options = DemangleOptions::default();
+        let function = "TTokenSet<EScriptObjectState>::GetNameByToken(EScriptObjectState) const";
+        assert_eq!(
+            demangle_local_static(
+                "skBadString$localstatic3$GetNameByToken__31TTokenSet<18EScriptObjectState>CF18EScriptObjectState",
+                &options
+            ),
+            Some(LocalStatic {
+                function: function.to_string(),
+                variable: Some("skBadString".to_string()),
+                index: Some(3),
+                is_guard: false,
+            })
+        );
+        assert_eq!(
+            demangle_local_static(
+                "init$localstatic4$GetNameByToken__31TTokenSet<18EScriptObjectState>CF18EScriptObjectState",
+                &options
+            ),
+            Some(LocalStatic {
+                function: function.to_string(),
+                variable: None,
+                index: Some(4),
+                is_guard: true,
+            })
+        );
+        let function = "nw4r::g3d::GetAnmPlayPolicy(nw4r::g3d::AnmPolicy)";
+        assert_eq!(
+            demangle_local_static(
+                "@LOCAL@GetAnmPlayPolicy__Q24nw4r3g3dFQ34nw4r3g3d9AnmPolicy@policyTable",
+                &options
+            ),
+            Some(LocalStatic {
+                function: function.to_string(),
+                variable: Some("policyTable".to_string()),
+                index: None,
+                is_guard: false,
+            })
+        );
+        assert_eq!(
+            demangle_local_static(
+                "@GUARD@GetAnmPlayPolicy__Q24nw4r3g3dFQ34nw4r3g3d9AnmPolicy@policyTable",
+                &options
+            ),
+            Some(LocalStatic {
+                function: function.to_string(),
+                variable: Some("policyTable".to_string()),
+                index: None,
+                is_guard: true,
+            })
+        );
+        assert_eq!(
+            demangle_local_static(
+                "@LOCAL@method__32@LOCAL@func__5OuterFi@LocalClassFv@sCount",
+                &options
+            )
+            .map(|local| local.function),
+            Some("Outer::func(int)::LocalClass::method()".to_string())
+        );
+        assert_eq!(demangle_local_static("GetNameByToken__9TTokenSetCFi", &options), None);
+        assert_eq!(demangle_local_static("x$localstatic99999999999$func__Fv", &options), None);
+    }
+
+    #[test]
+    fn test_demangle_spanned() {
+        let options = DemangleOptions::default();
+        assert_eq!(
+            demangle_spanned("IsEqual<i>__FRCiRCi_b", &options),
+            Some(Spanned {
+                demangled: "bool IsEqual<int>(const int&, const int&)".to_string(),
+                spans: vec![
+                    (0..4, 20..21, SpanKind::ReturnType),
+                    (5..17, 0..10, SpanKind::Name),
+                    (13..16, 8..9, SpanKind::TemplateArgument),
+                    (18..28, 13..16, SpanKind::Parameter),
+                    (18..23, 14..15, SpanKind::Qualifier),
+                    (27..28, 13..14, SpanKind::Qualifier),
+                    (30..40, 16..19, SpanKind::Parameter),
+                    (30..35, 17..18, SpanKind::Qualifier),
+                    (39..40, 16..17, SpanKind::Qualifier),
+                ],
+            })
+        );
+        let mangled = "Get__Q23std9Bar<s,-1>CFPA3_PCiPFPCc_v";
+        let spanned = demangle_spanned(mangled, &options).unwrap();
+        assert_eq!(
+            spanned.demangled,
+            "std::Bar<short, -1>::Get(const int*(*)[3], void (*)(const char*)) const"
+        );
+        let parts = spanned
+            .spans
+            .iter()
+            .filter(|(_, _, kind)| *kind != SpanKind::Qualifier)
+            .map(|(output, input, kind)| {
+                (&spanned.demangled[output.clone()], &mangled[input.clone()], *kind)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(parts, vec![
+            ("std", "3std", SpanKind::Name),
+            ("Bar<short, -1>", "9Bar<s,-1>", SpanKind::Name),
+            ("short", "s", SpanKind::TemplateArgument),
+            ("-1", "-1", SpanKind::TemplateArgument),
+            ("Get", "Get", SpanKind::Name),
+            ("const int*(*)[3]", "PA3_PCi", SpanKind::Parameter),
+            ("void (*)(const char*)", "PFPCc_v", SpanKind::Parameter),
+            ("void", "v", SpanKind::ReturnType),
+            ("const char*", "PCc", SpanKind::Parameter),
+        ]);
+        assert_eq!(
+            demangle_spanned("__dt__6CActorFv", &options).map(|s| s.spans),
+            Some(vec![(0..6, 6..13, SpanKind::Name), (8..15, 0..4, SpanKind::Name)])
+        );
+        assert_eq!(
+            demangle_spanned("__sinit_CGameState_cpp", &options).map(|s| s.spans),
+            Some(vec![])
+        );
+        assert_eq!(demangle_spanned("Update__6CActorFz", &options), None);
+    }
+
+    #[test]
+    fn test_demangle_tokens() {
+        use TokenKind::*;
+        let options = DemangleOptions::default();
+        let tokens = |str| {
+            demangle_tokens(str, &options)
+                .map(|t| t.iter().map(|(k, s)| (k, s.to_string())).collect())
+        };
+        let expected = |tokens: &[(TokenKind, &str)]| {
+            Some(tokens.iter().map(|&(k, s)| (k, s.to_string())).collect::<Vec<_>>())
+        };
+        assert_eq!(
+            tokens("__ls<i>__Q24rstl7ostreamFRQ24rstl7ostreami_RQ24rstl7ostream"),
+            expected(&[
+                (Namespace, "rstl"),
+                (Punctuation, "::"),
+                (Class, "ostream"),
+                (Punctuation, "&"),
+                (Whitespace, " "),
+                (Namespace, "rstl"),
+                (Punctuation, "::"),
+                (Class, "ostream"),
+                (Punctuation, "::"),
+                (Function, "operator<<"),
+                (Whitespace, " "),
+                (Punctuation, "<"),
+                (BuiltinType, "int"),
+                (Punctuation, ">("),
+                (Namespace, "rstl"),
+                (Punctuation, "::"),
+                (Class, "ostream"),
+                (Punctuation, "&,"),
+                (Whitespace, " "),
+                (BuiltinType, "int"),
+                (Punctuation, ")"),
+            ])
+        );
+        assert_eq!(
+            tokens("Get__Q23std9Bar<s,-1>CFPA3_UiPFPCc_v"),
+            expected(&[
+                (Namespace, "std"),
+                (Punctuation, "::"),
+                (Class, "Bar"),
+                (Punctuation, "<"),
+                (BuiltinType, "short"),
+                (Punctuation, ","),
+                (Whitespace, " "),
+                (Literal, "-1"),
+                (Punctuation, ">::"),
+                (Function, "Get"),
+                (Punctuation, "("),
+                (Keyword, "unsigned"),
+                (Whitespace, " "),
+                (BuiltinType, "int"),
+                (Punctuation, "(*)["),
+                (Literal, "3"),
+                (Punctuation, "],"),
+                (Whitespace, " "),
+                (BuiltinType, "void"),
+                (Whitespace, " "),
+                (Punctuation, "(*)("),
+                (Keyword, "const"),
+                (Whitespace, " "),
+                (BuiltinType, "char"),
+                (Punctuation, "*))"),
+                (Whitespace, " "),
+                (Keyword, "const"),
+            ])
+        );
+        assert_eq!(
+            tokens("init$localstatic4$GetNameByToken__9TTokenSetCFi"),
+            expected(&[
+                (Class, "TTokenSet"),
+                (Punctuation, "::"),
+                (Function, "GetNameByToken"),
+                (Punctuation, "("),
+                (BuiltinType, "int"),
+                (Punctuation, ")"),
+                (Whitespace, " "),
+                (Keyword, "const"),
+                (Punctuation, "::"),
+                (Variable, "localstatic4"),
+                (Whitespace, " "),
+                (Annotation, "guard"),
+            ])
+        );
+        assert_eq!(
+            tokens("mInstance__8CManager"),
+            expected(&[(Class, "CManager"), (Punctuation, "::"), (Variable, "mInstance")])
+        );
+        assert_eq!(
+            tokens("__sinit_CGameState_cpp"),
+            expected(&[
+                (Annotation, "static initializer for"),
+                (Whitespace, " "),
+                (Punctuation, "'"),
+                (Literal, "CGameState.cpp"),
+                (Punctuation, "'"),
+            ])
+        );
+        assert_eq!(tokens("Update__6CActorFz"), None);
+    }
+
+    #[test]
+    fn test_demangle_operators() {
+        let options = DemangleOptions::default();
+        let operators = [
+            ("nw", "operator new"),
+            ("nwa", "operator new[]"),
+            ("dl", "operator delete"),
+            ("dla", "operator delete[]"),
+            ("pl", "operator+"),
+            ("mi", "operator-"),
+            ("ml", "operator*"),
+            ("dv", "operator/"),
+            ("md", "operator%"),
+            ("er", "operator^"),
+            ("ad", "operator&"),
+            ("or", "operator|"),
+            ("co", "operator~"),
+            ("nt", "operator!"),
+            ("as", "operator="),
+            ("lt", "operator<"),
+            ("gt", "operator>"),
+            ("apl", "operator+="),
+            ("ami", "operator-="),
+            ("amu", "operator*="),
+            ("adv", "operator/="),
+            ("amd", "operator%="),
+            ("aer", "operator^="),
+            ("aad", "operator&="),
+            ("aor", "operator|="),
+            ("ls", "operator<<"),
+            ("rs", "operator>>"),
+            ("ars", "operator>>="),
+            ("als", "operator<<="),
+            ("eq", "operator=="),
+            ("ne", "operator!="),
+            ("le", "operator<="),
+            ("ge", "operator>="),
+            ("aa", "operator&&"),
+            ("oo", "operator||"),
+            ("pp", "operator++"),
+            ("mm", "operator--"),
+            ("cm", "operator,"),
+            ("rm", "operator->*"),
+            ("rf", "operator->"),
+            ("cl", "operator()"),
+            ("vc", "operator[]"),
+            ("vt", "__vtable"),
+        ];
+        for (code, name) in operators {
+            assert_eq!(
+                demangle(&format!("__{code}__5CTestFRC5CTest"), &options),
+                Some(format!("CTest::{name}(const CTest&)")),
+                "{code}"
+            );
+            // Template arguments with nested `<>`, a function type and a literal
+            let sep = if name.ends_with('<') { " " } else { "" };
+            assert_eq!(
+                demangle(
+                    &format!("__{code}<Q23std9vector<i>,PFi_v,-1>__5CTestFRC5CTest"),
+                    &options
+                ),
+                Some(format!(
+                    "CTest::{name}{sep}<std::vector<int>, void (*)(int), -1>(const CTest&)"
+                )),
+                "{code}"
+            );
+            assert_eq!(
+                demangle(&format!("__{code}<5CTest>__FRC5CTestRC5CTest_b"), &options),
+                Some(format!("bool {name}{sep}<CTest>(const CTest&, const CTest&)")),
+                "{code}"
+            );
+        }
+        assert_eq!(
+            demangle("__ct<Q23std9vector<i>>__5CTestFv", &options),
+            Some("CTest::CTest<std::vector<int>>()".to_string())
+        );
+        assert_eq!(demangle("__dt<1>__5CTestFv", &options), Some("CTest::~CTest<1>()".to_string()));
+        // Conversion operators, including targets containing `__` and `<>`
+        assert_eq!(
+            demangle("__opQ23std9vector<i>__5CTestCFv", &options),
+            Some("CTest::operator std::vector<int>() const".to_string())
+        );
+        assert_eq!(
+            demangle("__opPv<i>__5CTestFv", &options),
+            Some("CTest::operator void*<int>()".to_string())
+        );
+        assert_eq!(
+            demangle("__op11__foo_bar____5CTestFv", &options),
+            Some("CTest::operator __foo_bar__()".to_string())
+        );
+        // Not every special name starting with `op` is a conversion operator
+        assert_eq!(
+            demangle("__open_file__5CTestFv", &options),
+            Some("CTest::__open_file()".to_string())
+        );
+        // Template arguments of template arguments
+        assert_eq!(
+            demangle("__rs<11TShift<2,1>,5CTest>__FR5CTestRC5CTest", &options),
+            Some("operator>><TShift<2, 1>, CTest>(CTest&, const CTest&)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_demangle_bytes() {
+        let options = DemangleOptions::default();
+        let symbol = b"Update__10\x83\x76\x83\x8c\x83\x43\x83\x84\x81\x5bFv";
+        assert_eq!(
+            demangle_bytes(symbol, Encoding::ShiftJis, &options),
+            Some("プレイヤー::Update()".to_string())
+        );
+        assert_eq!(
+            demangle_bytes(symbol, Encoding::Escape, &options),
+            Some("\\x83v\\x83\\x8c\\x83C\\x83\\x84\\x81[::Update()".to_string())
+        );
+        assert_eq!(
+            demangle_bytes(symbol, Encoding::Utf8, &options),
+            Some("\\x83v\\x83\\x8c\\x83C\\x83\\x84\\x81[::Update()".to_string())
+        );
+        // Trail byte 0x5C must not be mistaken for a backslash
+        assert_eq!(
+            demangle_bytes(b"Draw__4\x95\x5c\x8e\xa6CFv", Encoding::ShiftJis, &options),
+            Some("表示::Draw() const".to_string())
+        );
+        // Half-width katakana
+        assert_eq!(
+            demangle_bytes(b"SetTarget__6CActorFP3\xb6\xd2\xd7", Encoding::ShiftJis, &options),
+            Some("CActor::SetTarget(ｶﾒﾗ*)".to_string())
+        );
+        assert_eq!(
+            demangle("Update__15プレイヤーFv", &options),
+            Some("プレイヤー::Update()".to_string())
+        );
+        // Length prefixes count bytes, not characters
+        assert_eq!(demangle("Update__5プレイヤーFv", &options), None);
+    }
+
+    #[test]
+    fn test_demangle_nesting_limit() {
+        let options = DemangleOptions::default();
+        let nested = |depth: usize| format!("fn__F{}v{}", "PF".repeat(depth), "_v".repeat(depth));
+        assert_eq!(
+            demangle(&nested(2), &options),
+            Some("fn(void (*)(void (*)(void)))".to_string())
+        );
+        assert!(demangle(&nested(MAX_DEPTH as usize / 2 - 1), &options).is_some());
+        assert_eq!(demangle(&nested(MAX_DEPTH as usize), &options), None);
+    }
+
     #[test]
     fn test_demangle_options() {
         let options = DemangleOptions { omit_empty_parameters: true, mw_extensions: false };
@@ -814,5 +2085,9 @@ mod tests {
             demangle("fn<3,PV2>__FPC2", &options),
             Some("fn<3, volatile __vec2x32float__*>(const __vec2x32float__*)".to_string())
         );
+        assert_eq!(
+            demangle("fn__FRCU1", &options),
+            Some("fn(const unsigned __int128&)".to_string())
+        );
     }
 }
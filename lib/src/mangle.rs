@@ -0,0 +1,744 @@
+//! Mangling C++ declarations, in the form [demangle](crate::demangle) prints
+//! them.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::MAX_DEPTH;
+
+/// Why a declaration couldn't be mangled, as returned by [mangle].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MangleError {
+    /// The byte offset in the declaration where the problem was found.
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for MangleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.position)
+    }
+}
+
+type Result<T> = core::result::Result<T, MangleError>;
+
+/// Operator names and their mangled codes. Longer names come first, so that
+/// each is matched before any name it starts with.
+const OPERATORS: &[(&str, &str)] = &[
+    ("new[]", "nwa"),
+    ("delete[]", "dla"),
+    ("new", "nw"),
+    ("delete", "dl"),
+    ("->*", "rm"),
+    ("->", "rf"),
+    ("()", "cl"),
+    ("[]", "vc"),
+    ("<<=", "als"),
+    (">>=", "ars"),
+    ("<<", "ls"),
+    (">>", "rs"),
+    ("==", "eq"),
+    ("!=", "ne"),
+    ("<=", "le"),
+    (">=", "ge"),
+    ("&&", "aa"),
+    ("||", "oo"),
+    ("++", "pp"),
+    ("--", "mm"),
+    ("+=", "apl"),
+    ("-=", "ami"),
+    ("*=", "amu"),
+    ("/=", "adv"),
+    ("%=", "amd"),
+    ("^=", "aer"),
+    ("&=", "aad"),
+    ("|=", "aor"),
+    ("+", "pl"),
+    ("-", "mi"),
+    ("*", "ml"),
+    ("/", "dv"),
+    ("%", "md"),
+    ("^", "er"),
+    ("&", "ad"),
+    ("|", "or"),
+    ("~", "co"),
+    ("!", "nt"),
+    ("=", "as"),
+    ("<", "lt"),
+    (">", "gt"),
+    (",", "cm"),
+];
+
+/// Builtin type names and their codes. Longer names come first.
+const BUILTIN_TYPES: &[(&str, &str)] = &[
+    ("long long", "x"),
+    ("long double", "r"),
+    ("void", "v"),
+    ("bool", "b"),
+    ("char", "c"),
+    ("short", "s"),
+    ("int", "i"),
+    ("long", "l"),
+    ("float", "f"),
+    ("double", "d"),
+    ("wchar_t", "w"),
+];
+
+/// One component of a qualified name.
+enum Component {
+    /// A name with its mangled template arguments, such as `vector<i>`.
+    Name { name: String, args: String },
+    /// A destructor, `~Class`, with its mangled template arguments.
+    Destructor { args: String },
+    /// An operator with its code, such as `eq`, or `op` and the target type for
+    /// conversion operators.
+    Operator { code: String, args: String },
+}
+
+/// A type being built up from a declarator.
+enum Type {
+    /// A function type, which is mangled differently once it's pointed to by a
+    /// member pointer. `params` is `None` for an empty parameter list.
+    Function {
+        params: Option<String>,
+        is_const: bool,
+        ret: String,
+    },
+    Other(String),
+}
+
+impl Type {
+    fn into_mangled(self) -> String {
+        match self {
+            Type::Function { params, ret, .. } => {
+                format!("F{}_{ret}", params.as_deref().unwrap_or("v"))
+            }
+            Type::Other(str) => str,
+        }
+    }
+}
+
+/// Part of a declarator, which turns the type before it into another.
+enum Op {
+    /// A pointer or reference, with the code for it and its qualifiers, such as
+    /// `CP`.
+    Pointer(String),
+    /// A pointer to a member of `class`, with the code for its qualifiers.
+    Member {
+        cv: String,
+        class: String,
+    },
+    Array(String),
+    Function {
+        params: Option<String>,
+        is_const: bool,
+    },
+}
+
+impl Op {
+    fn apply(self, ty: Type) -> Type {
+        match (self, ty) {
+            (Op::Member { cv, class }, Type::Function { params, is_const, ret }) => {
+                let this = if is_const { "PCvPCv" } else { "PCvPv" };
+                let params = params.unwrap_or_default();
+                Type::Other(format!("{cv}M{class}F{this}{params}_{ret}"))
+            }
+            (Op::Member { cv, class }, ty) => {
+                Type::Other(format!("{cv}M{class}{}", ty.into_mangled()))
+            }
+            (Op::Pointer(code), ty) => Type::Other(code + &ty.into_mangled()),
+            (Op::Array(size), ty) => Type::Other(format!("A{size}_{}", ty.into_mangled())),
+            (Op::Function { params, is_const }, ty) => {
+                Type::Function { params, is_const, ret: ty.into_mangled() }
+            }
+        }
+    }
+}
+
+/// Applies the declarator `ops` to the type `base`, innermost first.
+fn apply(base: String, ops: Vec<Op>) -> Type {
+    ops.into_iter().fold(Type::Other(base), |ty, op| op.apply(ty))
+}
+
+struct Parser<'a> {
+    str: &'a str,
+    pos: usize,
+    /// How many types and declarators are being parsed.
+    depth: u32,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &'static str) -> Result<T> {
+        Err(MangleError { position: self.pos, message })
+    }
+
+    fn rest(&self) -> &'a str { &self.str[self.pos..] }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Whether `token` comes next, without consuming it.
+    fn peek(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        self.rest().starts_with(token)
+    }
+
+    /// Consumes `token` if it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        if self.peek(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str, message: &'static str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            self.error(message)
+        }
+    }
+
+    /// Consumes `keyword` if it comes next as a whole word.
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let start = self.pos;
+        if self.eat(keyword) && !self.rest().starts_with(is_ident_char) {
+            return true;
+        }
+        self.pos = start;
+        false
+    }
+
+    /// Consumes any `const` and `volatile` keywords, returning their codes.
+    fn qualifiers(&mut self) -> String {
+        let mut cv = String::new();
+        loop {
+            if self.eat_keyword("const") {
+                cv += "C";
+            } else if self.eat_keyword("volatile") {
+                cv += "V";
+            } else {
+                return cv;
+            }
+        }
+    }
+
+    fn ident(&mut self) -> Result<&'a str> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return self.error("expected a name");
+        }
+        let len = rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len());
+        if len == 0 || &rest[..len] == "operator" {
+            return self.error("expected a name");
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    /// Parses a whole declaration, such as `CActor::SetActive(bool)` or
+    /// `bool IsEqual<int>(const int&, const int&)`.
+    fn declaration(&mut self) -> Result<String> {
+        for (prefix, kind) in
+            [("static initializer for '", "__sinit_"), ("static destructor for '", "__std__")]
+        {
+            if self.eat(prefix) {
+                let file = self.rest().strip_suffix('\'').filter(|f| !f.is_empty());
+                let file = match file {
+                    Some(file) => file,
+                    None => return self.error("expected a quoted file name"),
+                };
+                // The `.` before the extension is written as `_`
+                let file = match file.rfind('.') {
+                    Some(idx) => format!("{}_{}", &file[..idx], &file[idx + 1..]),
+                    None => file.to_string(),
+                };
+                return Ok(format!("{kind}{file}"));
+            }
+        }
+
+        // Only function templates have a return type, so try without one first,
+        // and report whichever error got further
+        let without = self.symbol(false);
+        if without.is_ok() {
+            return without;
+        }
+        self.pos = 0;
+        match (without, self.symbol(true)) {
+            (_, Ok(symbol)) => Ok(symbol),
+            (Err(a), Err(b)) if b.position > a.position => Err(b),
+            (without, _) => without,
+        }
+    }
+
+    /// Parses a function or variable, with a return type if `typed` is set.
+    fn symbol(&mut self, typed: bool) -> Result<String> {
+        let base = if typed { Some(self.type_specifier()?) } else { None };
+        let start = self.pos;
+        let (mut ops, components) = self.declarator(true)?;
+        let mut components = components.unwrap_or_default();
+        if self.peek("::") {
+            return self.error("function-local names can't be mangled");
+        }
+        self.skip_whitespace();
+        if !self.rest().is_empty() {
+            return self.error("unexpected text after declaration");
+        }
+
+        let function = match ops.pop() {
+            Some(Op::Function { params, is_const }) => Some((params, is_const)),
+            Some(op) => {
+                ops.push(op);
+                None
+            }
+            None => None,
+        };
+        if (base.is_some() && function.is_none()) || (base.is_none() && !ops.is_empty()) {
+            self.pos = start;
+            return self.error("expected a function");
+        }
+        let return_type = base.map(|base| apply(base, ops).into_mangled());
+        let name = match components.pop() {
+            Some(Component::Name { name, args }) => {
+                let class_name = match components.last() {
+                    Some(Component::Name { name, .. }) => Some(name.as_str()),
+                    _ => None,
+                };
+                if class_name == Some(name.as_str()) {
+                    format!("__ct{args}")
+                } else if name == "__vtable" {
+                    "__vt".to_string()
+                } else {
+                    name + &args
+                }
+            }
+            Some(Component::Destructor { args }) => format!("__dt{args}"),
+            Some(Component::Operator { code, args }) => format!("__{code}{args}"),
+            None => unreachable!(),
+        };
+        if components.is_empty() && function.is_none() {
+            // Global variables aren't mangled
+            return Ok(name);
+        }
+        let mut out = name + "__";
+        out += &self.encode_qualified(components, start)?;
+        if let Some((params, is_const)) = function {
+            if is_const {
+                out += "C";
+            }
+            out += "F";
+            out += params.as_deref().unwrap_or("v");
+        }
+        if let Some(return_type) = return_type {
+            out += "_";
+            out += &return_type;
+        }
+        Ok(out)
+    }
+
+    /// Parses a possibly qualified name. Destructors and operators are only
+    /// allowed if `special` is set.
+    fn qualified_name(&mut self, special: bool) -> Result<Vec<Component>> {
+        let mut components = Vec::new();
+        loop {
+            let component = if special && self.eat("~") {
+                self.ident()?;
+                Component::Destructor { args: self.template_args()? }
+            } else if special && self.eat_keyword("operator") {
+                self.operator()?
+            } else {
+                let name = self.ident()?.to_string();
+                let args = self.template_args()?;
+                Component::Name { name, args }
+            };
+            components.push(component);
+            // Stop before the `::*` of a pointer to member, and before special
+            // names when they aren't allowed
+            let start = self.pos;
+            if !self.eat("::") {
+                break;
+            }
+            if self.eat("*") || (!special && (self.eat("~") || self.eat_keyword("operator"))) {
+                self.pos = start;
+                break;
+            }
+        }
+        Ok(components)
+    }
+
+    /// Parses the operator following `operator`.
+    fn operator(&mut self) -> Result<Component> {
+        self.skip_whitespace();
+        for &(name, code) in OPERATORS {
+            let start = self.pos;
+            // `new[]` and `delete[]` may have a space before the brackets
+            let matched = if let Some((word, brackets)) = name.split_once('[') {
+                let found = self.eat_keyword(word) && self.eat(&format!("[{brackets}"));
+                if !found {
+                    self.pos = start;
+                }
+                found
+            } else if name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                self.eat_keyword(name)
+            } else {
+                self.eat(name)
+            };
+            if matched {
+                let args = self.template_args()?;
+                return Ok(Component::Operator { code: code.to_string(), args });
+            }
+        }
+        // Conversion operator, whose type can't be followed by parameters
+        let base = self.type_specifier()?;
+        let ty = apply(base, self.pointers()?).into_mangled();
+        Ok(Component::Operator { code: format!("op{ty}"), args: self.template_args()? })
+    }
+
+    /// Parses template arguments if they come next, returning them mangled.
+    fn template_args(&mut self) -> Result<String> {
+        if !self.eat("<") {
+            return Ok(String::new());
+        }
+        let mut out = String::from("<");
+        if self.eat(">") {
+            return Ok(out + ">");
+        }
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            let negative = rest.starts_with('-');
+            let digits = rest[negative as usize..]
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len() - negative as usize);
+            if digits > 0 {
+                let len = negative as usize + digits;
+                out += &rest[..len];
+                self.pos += len;
+            } else {
+                out += &self.type_()?;
+            }
+            if self.eat(",") {
+                out += ",";
+            } else {
+                self.expect(">", "expected `,` or `>`")?;
+                break;
+            }
+        }
+        Ok(out + ">")
+    }
+
+    /// Parses a parenthesized parameter list, returning it mangled, or `None` if
+    /// it's empty.
+    fn parameters(&mut self) -> Result<Option<String>> {
+        self.expect("(", "expected `(`")?;
+        if self.eat(")") {
+            return Ok(None);
+        }
+        let mut out = String::new();
+        loop {
+            out += &self.type_()?;
+            if self.eat(",") {
+                continue;
+            }
+            self.expect(")", "expected `,` or `)`")?;
+            return Ok(Some(out));
+        }
+    }
+
+    /// Parses a type, such as `const char*` or `void (*)(int)`, returning it
+    /// mangled.
+    fn type_(&mut self) -> Result<String> {
+        if self.eat("...") {
+            return Ok("e".to_string());
+        }
+        self.nested(|p| {
+            let base = p.type_specifier()?;
+            let (ops, _) = p.declarator(false)?;
+            Ok(apply(base, ops).into_mangled())
+        })
+    }
+
+    /// Runs `f` one level deeper, failing once [MAX_DEPTH] is exceeded.
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= MAX_DEPTH {
+            return self.error("too deeply nested");
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Parses the type a declarator applies to, such as `const char` or
+    /// `std::string`, returning it mangled.
+    fn type_specifier(&mut self) -> Result<String> {
+        // Qualifiers keep their place around the sign, as `UCl` demangles to
+        // `unsigned const long` and `CUl` to `const unsigned long`
+        let (mut cv, mut signed_cv) = (String::new(), String::new());
+        let mut sign = "";
+        loop {
+            let qualifiers = self.qualifiers();
+            if sign.is_empty() {
+                cv += &qualifiers;
+            } else {
+                signed_cv += &qualifiers;
+            }
+            if self.eat_keyword("signed") {
+                sign = "S";
+            } else if self.eat_keyword("unsigned") {
+                sign = "U";
+            } else {
+                break;
+            }
+        }
+        self.skip_whitespace();
+        let start = self.pos;
+        let base = match self.builtin_type() {
+            Some(code) if !sign.is_empty() && !matches!(code, "c" | "s" | "i" | "l" | "x") => {
+                self.pos = start;
+                return self.error("only integer types can be signed or unsigned");
+            }
+            Some(code) => code.to_string(),
+            None if !sign.is_empty() => "i".to_string(),
+            None => {
+                let components = self.qualified_name(false)?;
+                self.encode_qualified(components, start)?
+            }
+        };
+        cv += &self.qualifiers();
+        Ok(cv + sign + &signed_cv + &base)
+    }
+
+    fn builtin_type(&mut self) -> Option<&'static str> {
+        for &(name, code) in BUILTIN_TYPES {
+            let start = self.pos;
+            if name.split(' ').all(|word| self.eat_keyword(word)) {
+                return Some(code);
+            }
+            self.pos = start;
+        }
+        None
+    }
+
+    /// Parses a declarator, such as `*const&` in `char* const&` or
+    /// `(*Func(int))[3]`, which must contain a name if `named` is set.
+    ///
+    /// Returns the parts to apply to the type before it, innermost first, and the
+    /// name.
+    fn declarator(&mut self, named: bool) -> Result<(Vec<Op>, Option<Vec<Component>>)> {
+        let mut ops = self.pointers()?;
+        let mut inner = Vec::new();
+        let mut name = None;
+        // A parenthesized declarator starts with a pointer, otherwise the
+        // parentheses hold parameters
+        let start = self.pos;
+        if self.eat("(") && (self.peek("*") || self.peek("&") || self.at_member_pointer()) {
+            let (ops, inner_name) = self.nested(|p| p.declarator(named))?;
+            self.expect(")", "expected `)`")?;
+            inner = ops;
+            name = inner_name;
+        } else {
+            self.pos = start;
+            if named {
+                name = Some(self.qualified_name(true)?);
+            }
+        }
+        let mut suffixes = Vec::new();
+        loop {
+            if self.peek("(") {
+                let params = self.parameters()?;
+                let is_const = self.eat_keyword("const");
+                suffixes.push(Op::Function { params, is_const });
+            } else if self.eat("[") {
+                self.skip_whitespace();
+                let rest = self.rest();
+                let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                if len == 0 {
+                    return self.error("expected an array size");
+                }
+                self.pos += len;
+                suffixes.push(Op::Array(rest[..len].to_string()));
+                self.expect("]", "expected `]`")?;
+            } else {
+                break;
+            }
+        }
+        // `[2][3]` is an array of 2 arrays of 3
+        ops.extend(suffixes.into_iter().rev());
+        ops.extend(inner);
+        Ok((ops, name))
+    }
+
+    /// Parses a run of `*`, `&` and `Class::*`, each with any qualifiers
+    /// following it.
+    fn pointers(&mut self) -> Result<Vec<Op>> {
+        let mut ops = Vec::new();
+        loop {
+            let code = if self.eat("*") {
+                "P"
+            } else if self.eat("&") {
+                "R"
+            } else if self.at_member_pointer() {
+                self.skip_whitespace();
+                let start = self.pos;
+                let class = self.qualified_name(false)?;
+                self.expect("::", "expected `::*`")?;
+                self.expect("*", "expected `::*`")?;
+                let class = self.encode_qualified(class, start)?;
+                ops.push(Op::Member { cv: self.qualifiers(), class });
+                continue;
+            } else {
+                return Ok(ops);
+            };
+            ops.push(Op::Pointer(self.qualifiers() + code));
+        }
+    }
+
+    /// Whether a pointer to member such as `CActor::*` comes next. This only
+    /// scans ahead, since parsing the class name twice could take exponential
+    /// time for nested templates.
+    fn at_member_pointer(&mut self) -> bool {
+        self.skip_whitespace();
+        let mut depth = 0usize;
+        let rest = self.rest();
+        for (i, c) in rest.char_indices() {
+            match c {
+                '<' | '(' => depth += 1,
+                '>' | ')' if depth > 0 => depth -= 1,
+                ':' if depth == 0 && rest[i..].starts_with("::") => {
+                    if rest[i + 2..].trim_start().starts_with('*') {
+                        return true;
+                    }
+                }
+                c if depth > 0 || is_ident_char(c) || c.is_whitespace() || c == ':' => {}
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    /// Mangles a qualified class name that started at `start`.
+    fn encode_qualified(&self, components: Vec<Component>, start: usize) -> Result<String> {
+        let mut out = String::new();
+        if components.len() > 9 {
+            return Err(MangleError { position: start, message: "too many qualifiers" });
+        }
+        if components.len() > 1 {
+            out += &format!("Q{}", components.len());
+        }
+        for component in components {
+            match component {
+                Component::Name { name, args } => {
+                    out += &format!("{}{name}{args}", name.len() + args.len());
+                }
+                _ => {
+                    return Err(MangleError {
+                        position: start,
+                        message: "operators and destructors can't qualify a name",
+                    })
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$' || !c.is_ascii()
+}
+
+/// Mangle a C++ declaration, such as `CActor::SetActive(bool)`.
+///
+/// Declarations are accepted in the form [demangle](crate::demangle) prints
+/// them, so names and types must be fully qualified, and only function
+/// templates may have a return type.
+pub fn mangle(str: &str) -> core::result::Result<String, MangleError> {
+    Parser { str, pos: 0, depth: 0 }.declaration()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{demangle, DemangleOptions};
+
+    #[test]
+    fn test_mangle() {
+        let ok = |str| mangle(str).map_err(|e| e.to_string());
+        assert_eq!(ok("CActor::SetActive(bool)"), Ok("SetActive__6CActorFb".to_string()));
+        assert_eq!(ok("CActor::CActor()"), Ok("__ct__6CActorFv".to_string()));
+        assert_eq!(ok("CActor::~CActor()"), Ok("__dt__6CActorFv".to_string()));
+        assert_eq!(
+            ok("bool IsEqual<int>(const int&, const int&)"),
+            Ok("IsEqual<i>__FRCiRCi_b".to_string())
+        );
+        assert_eq!(ok("CActor::mInstance"), Ok("mInstance__6CActor".to_string()));
+        assert_eq!(ok("gGlobal"), Ok("gGlobal".to_string()));
+        assert_eq!(
+            ok("static initializer for 'CGameState.cpp'"),
+            Ok("__sinit_CGameState_cpp".to_string())
+        );
+        assert_eq!(
+            mangle("CActor::SetActive(bool"),
+            Err(MangleError { position: 22, message: "expected `,` or `)`" })
+        );
+        assert_eq!(
+            mangle("CActor::SetActive(bool) x"),
+            Err(MangleError { position: 24, message: "unexpected text after declaration" })
+        );
+        assert_eq!(
+            mangle("CActor::SetActive(4)"),
+            Err(MangleError { position: 18, message: "expected a name" })
+        );
+        assert_eq!(
+            mangle("f(unsigned wchar_t)"),
+            Err(MangleError {
+                position: 11,
+                message: "only integer types can be signed or unsigned"
+            })
+        );
+        assert_eq!(ok("f(unsigned long const*)"), Ok("f__FPCUl".to_string()));
+        let deep = format!("f({}int{})", "A<".repeat(100), ">".repeat(100));
+        assert_eq!(mangle(&deep).map_err(|e| e.message), Err("too deeply nested"));
+    }
+
+    #[test]
+    fn test_mangle_round_trip() {
+        let options = DemangleOptions::default();
+        for symbol in [
+            "Get__Q23std9Bar<s,-1>CFPA3_PCiPFPCc_v",
+            "__dt__26__partial_array_destructorFv",
+            "__opb__6CActorCFv",
+            "__opPCc__6CActorCFv",
+            "__nwa__FUlPv",
+            "__dla__FPv",
+            "__lt<i>__FRCiRCi",
+            "__ls__Q23std7ostreamFRCQ23std6string",
+            "__vt__6CActor",
+            "Func__FPFv_vPCPCcRCPc",
+            "Func__FPM6CActorFPCvPCvi_vM6CActorFPCvPv_v",
+            "Func__FA2_A3_iPA4_Ul",
+            "Func__FUcScUsUiUxrwe",
+            "Func__FVPVi",
+            "Func__FPUClPCUlPCUc",
+            "BareFn__FPFPCcPv_v_PFPCvPv_v",
+            "SomeFn__FRCPFPFPCvPv_v_RCPFPCvPv_v",
+            "__ct<Q23std9vector<i>>__5CTestFv",
+            "__dt<1>__5CTestFv",
+            "__opPv<i>__5CTestFv",
+            "abs__3stdFRCQ23std10complex<r>",
+            "__sinit_CGameState_cpp",
+        ] {
+            let demangled = demangle(symbol, &options).unwrap();
+            assert_eq!(mangle(&demangled).as_deref(), Ok(symbol), "{demangled}");
+        }
+    }
+}
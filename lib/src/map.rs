@@ -0,0 +1,352 @@
+//! Parsing CodeWarrior linker map files.
+
+use core::ops::Range;
+
+/// What a line of a map file describes, as returned by [parse_map].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapEntry<'a> {
+    /// A symbol in the link tree, such as
+    /// `  3] __init_registers (func,local) found in os.a __start.c`.
+    LinkTree {
+        /// How deeply the symbol is nested in the tree, starting from 1.
+        depth: u32,
+        name: &'a str,
+        /// The symbol type, such as `func` or `object`.
+        kind: Option<&'a str>,
+        /// The symbol binding, such as `global`, `local` or `weak`.
+        binding: Option<&'a str>,
+        /// Where the symbol was found, such as `os.a __start.c`. `None` for
+        /// linker generated symbols.
+        object: Option<&'a str>,
+    },
+    /// A symbol in a section layout, such as
+    /// `  00000000 000144 80003100  4 __start os.a __start.c`.
+    Symbol {
+        /// The section the symbol is laid out in.
+        section: &'a str,
+        /// The offset in the section, or `None` if the symbol was stripped as
+        /// unused.
+        address: Option<u32>,
+        size: u32,
+        /// The address once loaded, or `None` if the symbol was stripped.
+        virtual_address: Option<u32>,
+        /// The offset in the output file. Only written by later linkers.
+        file_offset: Option<u32>,
+        /// The symbol's alignment. Not written for unused symbols or entry points.
+        alignment: Option<u32>,
+        name: &'a str,
+        /// For entry points into another function, that function's name.
+        entry_of: Option<&'a str>,
+        /// Where the symbol was found, such as `os.a __start.c`.
+        object: &'a str,
+    },
+    /// A symbol in the list of linker generated symbols, such as
+    /// `         _stack_addr 803e8f80`.
+    LinkerGenerated { name: &'a str, address: u32 },
+    /// Any other line, such as a heading or the memory map.
+    Other,
+}
+
+/// A line of a map file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapLine<'a> {
+    /// The line, without its line ending.
+    pub text: &'a str,
+    /// The line ending, which is empty for the last line if it has none.
+    pub newline: &'a str,
+    pub entry: MapEntry<'a>,
+}
+
+impl<'a> MapLine<'a> {
+    /// The byte ranges of the symbol names in [text](Self::text), in order.
+    pub fn name_ranges(&self) -> impl Iterator<Item = Range<usize>> + 'a {
+        let names = match self.entry {
+            MapEntry::LinkTree { name, .. } | MapEntry::LinkerGenerated { name, .. } => {
+                [Some(name), None]
+            }
+            MapEntry::Symbol { name, entry_of, .. } => [Some(name), entry_of],
+            MapEntry::Other => [None, None],
+        };
+        let base = self.text.as_ptr() as usize;
+        names.into_iter().flatten().map(move |name| {
+            let start = name.as_ptr() as usize - base;
+            start..start + name.len()
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Part<'a> {
+    LinkTree,
+    SectionLayout(&'a str),
+    MemoryMap,
+    LinkerGenerated,
+}
+
+/// An iterator over the lines of a map file, returned by [parse_map].
+#[derive(Clone)]
+pub struct MapLines<'a> {
+    rest: &'a str,
+    part: Part<'a>,
+}
+
+/// Parses a CodeWarrior linker map, as written by the GameCube and Wii versions
+/// of `mwldeppc`, into its lines.
+///
+/// Lines that can't be parsed are returned as [MapEntry::Other], so every line of
+/// the input is returned and the map can be rewritten exactly.
+pub fn parse_map(text: &str) -> MapLines<'_> { MapLines { rest: text, part: Part::LinkTree } }
+
+impl<'a> Iterator for MapLines<'a> {
+    type Item = MapLine<'a>;
+
+    fn next(&mut self) -> Option<MapLine<'a>> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let len = self.rest.find('\n').map_or(self.rest.len(), |i| i + 1);
+        let (line, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        let text = line.trim_end_matches(['\r', '\n']);
+        let newline = &line[text.len()..];
+        let trimmed = text.trim();
+        if let Some(section) = trimmed.strip_suffix(" section layout") {
+            self.part = Part::SectionLayout(section);
+        } else if trimmed == "Memory map:" {
+            self.part = Part::MemoryMap;
+        } else if trimmed == "Linker generated symbols:" {
+            self.part = Part::LinkerGenerated;
+        } else if trimmed.starts_with("Link map of ") {
+            self.part = Part::LinkTree;
+        }
+        let entry = match self.part {
+            Part::LinkTree => parse_link_tree(trimmed),
+            Part::SectionLayout(section) => parse_symbol(section, trimmed),
+            Part::LinkerGenerated => parse_linker_generated(trimmed),
+            Part::MemoryMap => None,
+        };
+        Some(MapLine { text, newline, entry: entry.unwrap_or(MapEntry::Other) })
+    }
+}
+
+/// Parses `3] name (func,global) found in object` or
+/// `3] name found as linker generated symbol`.
+fn parse_link_tree(line: &str) -> Option<MapEntry<'_>> {
+    let (depth, rest) = line.split_once("] ")?;
+    let depth = depth.parse().ok()?;
+    let (name, rest) = split_word(rest)?;
+    if rest == "found as linker generated symbol" {
+        return Some(MapEntry::LinkTree { depth, name, kind: None, binding: None, object: None });
+    }
+    let (info, object) = rest.strip_prefix('(')?.split_once(") found in ")?;
+    let (kind, binding) = info.split_once(',')?;
+    let object = Some(object.trim());
+    Some(MapEntry::LinkTree { depth, name, kind: Some(kind), binding: Some(binding), object })
+}
+
+/// Parses a section layout row, with or without the file offset column:
+/// `00000000 000144 80003100 00000100  4 name object`,
+/// `UNUSED   000038 ........ name object` or
+/// `00000140 000004 80003240    name (entry of parent) object`.
+fn parse_symbol<'a>(section: &'a str, line: &'a str) -> Option<MapEntry<'a>> {
+    let (address, rest) = split_word(line)?;
+    let address = if address == "UNUSED" { None } else { Some(parse_hex(address, 8)?) };
+    let (size, rest) = split_word(rest)?;
+    let size = parse_hex(size, 0)?;
+    let (virtual_address, mut rest) = split_word(rest)?;
+    let virtual_address =
+        if virtual_address == "........" { None } else { Some(parse_hex(virtual_address, 8)?) };
+    let mut file_offset = None;
+    let mut alignment = None;
+    if let Some((word, next)) = split_word(rest) {
+        // Unused symbols have no file offset either
+        if word == "........" {
+            rest = next;
+        } else if let Some(offset) = parse_hex(word, 8) {
+            file_offset = Some(offset);
+            rest = next;
+        }
+    }
+    if let Some((word, next)) = split_word(rest) {
+        if let Ok(align) = word.parse() {
+            alignment = Some(align);
+            rest = next;
+        }
+    }
+    let (name, mut rest) = split_word(rest).unwrap_or((rest, ""));
+    if name.is_empty() {
+        return None;
+    }
+    let mut entry_of = None;
+    if let Some(entry) = rest.strip_prefix("(entry of ") {
+        let (parent, next) = entry.split_once(')')?;
+        entry_of = Some(parent);
+        rest = next.trim_start();
+    }
+    Some(MapEntry::Symbol {
+        section,
+        address,
+        size,
+        virtual_address,
+        file_offset,
+        alignment,
+        name,
+        entry_of,
+        object: rest.trim_end(),
+    })
+}
+
+/// Parses `name 803e8f80`.
+fn parse_linker_generated(line: &str) -> Option<MapEntry<'_>> {
+    let (name, address) = split_word(line)?;
+    let address = parse_hex(address, 8)?;
+    Some(MapEntry::LinkerGenerated { name, address })
+}
+
+/// Splits off the first word of `str`, returning it and the rest with leading
+/// whitespace removed.
+fn split_word(str: &str) -> Option<(&str, &str)> {
+    let idx = str.find(char::is_whitespace)?;
+    Some((&str[..idx], str[idx..].trim_start()))
+}
+
+/// Parses a hexadecimal number, which must be `len` digits long unless `len` is 0.
+fn parse_hex(str: &str, len: usize) -> Option<u32> {
+    if (len != 0 && str.len() != len) || !str.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(str, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use super::*;
+
+    const MAP: &str = "Link map of __start\r
+ 1] __start (func,global) found in os.a __start.c\r
+  2] __init_registers (func,local) found in os.a __start.c\r
+   3] _stack_addr found as linker generated symbol\r
+\r
+\r
+.init section layout\r
+  Starting        Virtual\r
+  address  Size   address\r
+  -----------------------\r
+  00000000 000144 80003100  4 __start \tos.a __start.c\r
+  UNUSED   000038 ........ Update__6CActorFv main.o \r
+  UNUSED   000024 ........ ........    __dt__6CActorFv main.o \r
+  00000140 000004 80003240    __fill_mem (entry of memset__FPvii) \tRuntime.PPCEABI.H.a mem.c\r
+\r
+.text section layout\r
+  00000000 0000a4 80004000 000005a0  1 .text \tmain.o \r
+\r
+Memory map:\r
+                   Starting Size     File\r
+           .init   80003100 000024e0 00000100\r
+\r
+Linker generated symbols:\r
+         _stack_addr 803e8f80";
+
+    #[test]
+    fn test_parse_map() {
+        let lines = parse_map(MAP).collect::<Vec<_>>();
+        assert_eq!(lines.iter().map(|l| [l.text, l.newline].concat()).collect::<String>(), MAP);
+        let entries =
+            lines.iter().map(|l| l.entry).filter(|e| *e != MapEntry::Other).collect::<Vec<_>>();
+        assert_eq!(entries, [
+            MapEntry::LinkTree {
+                depth: 1,
+                name: "__start",
+                kind: Some("func"),
+                binding: Some("global"),
+                object: Some("os.a __start.c"),
+            },
+            MapEntry::LinkTree {
+                depth: 2,
+                name: "__init_registers",
+                kind: Some("func"),
+                binding: Some("local"),
+                object: Some("os.a __start.c"),
+            },
+            MapEntry::LinkTree {
+                depth: 3,
+                name: "_stack_addr",
+                kind: None,
+                binding: None,
+                object: None,
+            },
+            MapEntry::Symbol {
+                section: ".init",
+                address: Some(0),
+                size: 0x144,
+                virtual_address: Some(0x80003100),
+                file_offset: None,
+                alignment: Some(4),
+                name: "__start",
+                entry_of: None,
+                object: "os.a __start.c",
+            },
+            MapEntry::Symbol {
+                section: ".init",
+                address: None,
+                size: 0x38,
+                virtual_address: None,
+                file_offset: None,
+                alignment: None,
+                name: "Update__6CActorFv",
+                entry_of: None,
+                object: "main.o",
+            },
+            MapEntry::Symbol {
+                section: ".init",
+                address: None,
+                size: 0x24,
+                virtual_address: None,
+                file_offset: None,
+                alignment: None,
+                name: "__dt__6CActorFv",
+                entry_of: None,
+                object: "main.o",
+            },
+            MapEntry::Symbol {
+                section: ".init",
+                address: Some(0x140),
+                size: 4,
+                virtual_address: Some(0x80003240),
+                file_offset: None,
+                alignment: None,
+                name: "__fill_mem",
+                entry_of: Some("memset__FPvii"),
+                object: "Runtime.PPCEABI.H.a mem.c",
+            },
+            MapEntry::Symbol {
+                section: ".text",
+                address: Some(0),
+                size: 0xa4,
+                virtual_address: Some(0x80004000),
+                file_offset: Some(0x5a0),
+                alignment: Some(1),
+                name: ".text",
+                entry_of: None,
+                object: "main.o",
+            },
+            MapEntry::LinkerGenerated { name: "_stack_addr", address: 0x803e8f80 },
+        ]);
+        let names =
+            lines.iter().flat_map(|l| l.name_ranges().map(move |r| &l.text[r])).collect::<Vec<_>>();
+        assert_eq!(names, [
+            "__start",
+            "__init_registers",
+            "_stack_addr",
+            "__start",
+            "Update__6CActorFv",
+            "__dt__6CActorFv",
+            "__fill_mem",
+            "memset__FPvii",
+            ".text",
+            "_stack_addr",
+        ]);
+    }
+}
@@ -0,0 +1,95 @@
+//! Demangled symbols broken down into their parts.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::LocalStatic;
+
+/// What kind of entity a symbol names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A free function or member function.
+    Function,
+    /// A constructor.
+    Constructor,
+    /// A destructor.
+    Destructor,
+    /// An overloaded operator, such as `operator==` or `operator new`.
+    Operator,
+    /// A conversion operator, such as `operator bool`.
+    Conversion,
+    /// A compiler-generated symbol with a special name, such as `__vtable`.
+    Special,
+    /// A global or static member variable.
+    Variable,
+    /// A function-local static variable or its guard variable.
+    LocalStatic,
+    /// A per-file static initializer function.
+    StaticInitializer,
+    /// A per-file static destructor function.
+    StaticDestructor,
+}
+
+impl SymbolKind {
+    /// A short lowercase name for the kind, such as `function` or `local_static`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SymbolKind::Function => "function",
+            SymbolKind::Constructor => "constructor",
+            SymbolKind::Destructor => "destructor",
+            SymbolKind::Operator => "operator",
+            SymbolKind::Conversion => "conversion",
+            SymbolKind::Special => "special",
+            SymbolKind::Variable => "variable",
+            SymbolKind::LocalStatic => "local_static",
+            SymbolKind::StaticInitializer => "static_initializer",
+            SymbolKind::StaticDestructor => "static_destructor",
+        }
+    }
+}
+
+/// A demangled symbol along with its parts, as returned by
+/// [demangle_parts](crate::demangle_parts).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parts {
+    /// The full demangled name.
+    pub demangled: String,
+    /// What the symbol names.
+    pub kind: SymbolKind,
+    /// The classes or namespaces the symbol is a member of, outermost first, each
+    /// with its template arguments.
+    pub class_path: Vec<String>,
+    /// The unqualified name with its template arguments, such as `Update`,
+    /// `~CActor` or `operator==`. For static initializers, the source file name.
+    pub name: String,
+    /// The demangled parameter types, or `None` if the symbol isn't a function.
+    pub parameters: Option<Vec<String>>,
+    /// Each of [parameters](Self::parameters) broken down into its modifiers.
+    pub parameter_types: Option<Vec<TypeParts>>,
+    /// The return type, if the mangled name records one.
+    pub return_type: Option<String>,
+    /// The [return type](Self::return_type) broken down into its modifiers.
+    pub return_type_parts: Option<TypeParts>,
+    /// Whether this is a `const` member function.
+    pub is_const: bool,
+    /// The enclosing function and variable, for function-local statics.
+    pub local_static: Option<LocalStatic>,
+}
+
+/// A pointer, reference or qualifier applied to a type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeModifier {
+    Pointer,
+    Reference,
+    Const,
+    Volatile,
+}
+
+/// A type broken down into the modifiers applied to it and the type they apply
+/// to, such as `[Reference, Const]` and `CVector3f` for `const CVector3f&`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeParts {
+    /// The pointers, references and qualifiers, outermost first.
+    pub modifiers: Vec<TypeModifier>,
+    /// The demangled type they apply to.
+    pub base: String,
+}
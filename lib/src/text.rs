@@ -0,0 +1,253 @@
+//! Demangled text annotated with the parts of the mangled name it came from and
+//! the kind of each token.
+
+use alloc::{string::String, vec::Vec};
+use core::ops::{Add, AddAssign, Range};
+
+/// The kind of demangled output a span covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    /// A component of a qualified name, such as a namespace, class or function
+    /// name, including its template arguments.
+    Name,
+    /// A single template argument.
+    TemplateArgument,
+    /// A single function parameter.
+    Parameter,
+    /// A function's return type.
+    ReturnType,
+    /// A qualifier such as `const`, `unsigned`, `*` or `&`.
+    Qualifier,
+}
+
+/// The syntactic kind of a token in demangled output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A scope qualifying a name. CodeWarrior doesn't distinguish namespaces
+    /// from classes, so this is every component of a qualified name but the last.
+    Namespace,
+    /// The name of a class or other named type.
+    Class,
+    /// The name of a function, including operators, constructors and destructors.
+    Function,
+    /// The name of a variable.
+    Variable,
+    /// A keyword, such as `const`, `unsigned` or `operator`.
+    Keyword,
+    /// A builtin type, such as `int` or `long double`.
+    BuiltinType,
+    /// Punctuation, such as `::`, `<`, `*` or `,`.
+    Punctuation,
+    /// A numeric literal, such as a template argument or array size.
+    Literal,
+    /// Whitespace between other tokens.
+    Whitespace,
+    /// Descriptive text that isn't part of a C++ name, such as `guard`.
+    Annotation,
+}
+
+/// A demangled name split into tokens, as returned by
+/// [demangle_tokens](crate::demangle_tokens).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tokens {
+    /// The demangled name.
+    pub demangled: String,
+    /// The kind and byte range in `demangled` of each token, in order. The tokens
+    /// cover the whole name.
+    pub tokens: Vec<(TokenKind, Range<usize>)>,
+}
+
+impl Tokens {
+    /// Iterates over the kind and text of each token.
+    pub fn iter(&self) -> impl Iterator<Item = (TokenKind, &str)> {
+        self.tokens.iter().map(move |(kind, range)| (*kind, &self.demangled[range.clone()]))
+    }
+}
+
+/// A demangled name along with the parts of the mangled name that produced it,
+/// as returned by [demangle_spanned](crate::demangle_spanned).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spanned {
+    /// The demangled name.
+    pub demangled: String,
+    /// `(output_range, input_range, kind)` for each part of the demangled name.
+    ///
+    /// Ranges are byte offsets into `demangled` and the mangled name. Spans nest,
+    /// e.g. a parameter contains the names making up its type; they are ordered by
+    /// output start, with outer spans first.
+    pub spans: Vec<(Range<usize>, Range<usize>, SpanKind)>,
+}
+
+/// Demangled text built up during parsing, with spans and tokens relative to its
+/// start. Punctuation and whitespace tokens are left implicit.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Text {
+    pub(crate) str: String,
+    pub(crate) spans: Vec<(Range<usize>, Range<usize>, SpanKind)>,
+    tokens: Vec<(TokenKind, Range<usize>)>,
+}
+
+impl Text {
+    pub(crate) fn new() -> Self { Text::default() }
+
+    /// Creates text consisting of a single token.
+    pub(crate) fn token(kind: TokenKind, str: &str) -> Self {
+        let mut text = Text::from(str);
+        if !str.is_empty() {
+            text.tokens.push((kind, 0..str.len()));
+        }
+        text
+    }
+
+    pub(crate) fn as_str(&self) -> &str { &self.str }
+
+    pub(crate) fn is_empty(&self) -> bool { self.str.is_empty() }
+
+    /// Records that all of the text was produced by `input`.
+    pub(crate) fn span(&mut self, kind: SpanKind, input: Range<usize>) {
+        if !self.str.is_empty() {
+            self.spans.push((0..self.str.len(), input, kind));
+        }
+    }
+
+    /// Splits the text at byte offset `mid`, clipping the spans and tokens to each
+    /// side.
+    pub(crate) fn split_at(self, mid: usize) -> (Text, Text) {
+        let mut left = Text { str: self.str, ..Text::default() };
+        let mut right = Text { str: left.str.split_off(mid), ..Text::default() };
+        for (kind, range) in self.tokens {
+            if range.start < mid {
+                left.tokens.push((kind, range.start..range.end.min(mid)));
+            }
+            if range.end > mid {
+                right.tokens.push((kind, range.start.max(mid) - mid..range.end - mid));
+            }
+        }
+        for (output, input, kind) in self.spans {
+            if output.start < mid {
+                left.spans.push((output.start..output.end.min(mid), input.clone(), kind));
+            }
+            if output.end > mid {
+                right.spans.push((output.start.max(mid) - mid..output.end - mid, input, kind));
+            }
+        }
+        (left, right)
+    }
+
+    pub(crate) fn trim_start(self) -> Text {
+        let len = self.str.len() - self.str.trim_start().len();
+        self.split_at(len).1
+    }
+
+    pub(crate) fn trim_end(self) -> Text {
+        let len = self.str.trim_end().len();
+        self.split_at(len).0
+    }
+
+    /// Returns the text of each span of `kind` that isn't inside another one, in
+    /// order.
+    pub(crate) fn outer_spans(&self, kind: SpanKind) -> Vec<String> {
+        self.outer_span_ranges(kind)
+            .into_iter()
+            .map(|(output, _)| self.str[output].into())
+            .collect()
+    }
+
+    /// Returns the output and input ranges of each span of `kind` that isn't
+    /// inside another one, in order.
+    pub(crate) fn outer_span_ranges(&self, kind: SpanKind) -> Vec<(Range<usize>, Range<usize>)> {
+        let mut spans: Vec<(Range<usize>, Range<usize>)> = self
+            .spans
+            .iter()
+            .filter(|(_, _, k)| *k == kind)
+            .map(|(output, input, _)| (output.clone(), input.clone()))
+            .collect();
+        spans.sort_by(|(a, _), (b, _)| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+        let mut end = 0;
+        let mut out = Vec::new();
+        for (output, input) in spans {
+            if output.start >= end {
+                end = output.end;
+                out.push((output, input));
+            }
+        }
+        out
+    }
+
+    pub(crate) fn into_spanned(self) -> Spanned {
+        let mut spans = self.spans;
+        // Outer spans are recorded after the spans they contain
+        spans.reverse();
+        spans.sort_by(|a, b| a.0.start.cmp(&b.0.start).then(b.0.end.cmp(&a.0.end)));
+        Spanned { demangled: self.str, spans }
+    }
+
+    /// Converts to [Tokens], treating any text outside of the recorded tokens as
+    /// punctuation and whitespace.
+    pub(crate) fn into_tokens(self) -> Tokens {
+        let mut tokens = Vec::with_capacity(self.tokens.len() * 2);
+        let mut end = 0;
+        for (kind, range) in self.tokens {
+            lex_punctuation(&self.str, end..range.start, &mut tokens);
+            end = range.end;
+            tokens.push((kind, range));
+        }
+        lex_punctuation(&self.str, end..self.str.len(), &mut tokens);
+        Tokens { demangled: self.str, tokens }
+    }
+}
+
+/// Splits `range` of `str` into runs of punctuation and whitespace.
+fn lex_punctuation(str: &str, range: Range<usize>, tokens: &mut Vec<(TokenKind, Range<usize>)>) {
+    let mut start = range.start;
+    while start < range.end {
+        let whitespace = str[start..].starts_with(char::is_whitespace);
+        let len = str[start..range.end]
+            .find(|c: char| c.is_whitespace() != whitespace)
+            .unwrap_or(range.end - start);
+        let kind = if whitespace { TokenKind::Whitespace } else { TokenKind::Punctuation };
+        tokens.push((kind, start..start + len));
+        start += len;
+    }
+}
+
+impl From<&str> for Text {
+    fn from(str: &str) -> Self { Text { str: str.into(), ..Text::default() } }
+}
+
+impl AddAssign<&str> for Text {
+    fn add_assign(&mut self, rhs: &str) { self.str += rhs; }
+}
+
+impl AddAssign<Text> for Text {
+    fn add_assign(&mut self, rhs: Text) {
+        let offset = self.str.len();
+        self.str += &rhs.str;
+        self.tokens.extend(
+            rhs.tokens
+                .into_iter()
+                .map(|(kind, range)| (kind, range.start + offset..range.end + offset)),
+        );
+        self.spans.extend(rhs.spans.into_iter().map(|(output, input, kind)| {
+            (output.start + offset..output.end + offset, input, kind)
+        }));
+    }
+}
+
+impl Add<&str> for Text {
+    type Output = Text;
+
+    fn add(mut self, rhs: &str) -> Text {
+        self += rhs;
+        self
+    }
+}
+
+impl Add<Text> for Text {
+    type Output = Text;
+
+    fn add(mut self, rhs: Text) -> Text {
+        self += rhs;
+        self
+    }
+}
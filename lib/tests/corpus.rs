@@ -0,0 +1,46 @@
+//! Checks the `mangled<TAB>expected` corpora in `tests/corpus`, in the format
+//! read by `cwdemangle check`. Entries with no expected column should fail.
+use std::fs;
+
+use cwdemangle::{demangle, mangle, DemangleOptions};
+
+/// Each corpus entry, as its location, mangled name and expected demangling.
+fn corpus() -> Vec<(String, String, Option<String>)> {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus");
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let text = fs::read_to_string(&path).unwrap();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (mangled, expected) = match line.split_once('\t') {
+                Some((mangled, expected)) => (mangled, Some(expected).filter(|e| !e.is_empty())),
+                None => (line, None),
+            };
+            let location = format!("{}:{}", path.display(), i + 1);
+            entries.push((location, mangled.to_string(), expected.map(str::to_string)));
+        }
+    }
+    assert!(!entries.is_empty(), "no corpus entries found in {dir}");
+    entries
+}
+
+#[test]
+fn test_corpus() {
+    for (location, mangled, expected) in corpus() {
+        let actual = demangle(&mangled, &DemangleOptions::default());
+        assert_eq!(actual, expected, "{location}");
+    }
+}
+
+/// Mangling each demangled name gives back the symbol it came from.
+#[test]
+fn test_corpus_round_trip() {
+    for (location, mangled, expected) in corpus() {
+        if let Some(expected) = expected {
+            assert_eq!(mangle(&expected).as_deref(), Ok(mangled.as_str()), "{location}");
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Replays inputs from `fuzz/regressions` that previously crashed the demangler,
+//! and the malformed inputs in `fuzz/edge-cases`, through every entry point.
+use std::{fs, path::Path};
+
+use cwdemangle::{
+    demangle, demangle_affixed, demangle_bytes, demangle_local_static, demangle_parts,
+    demangle_spanned, demangle_tokens, demangle_type_name, explain, mangle, DemangleOptions,
+    Encoding, DEFAULT_AFFIXES,
+};
+
+/// Each input and what it guards against.
+const INPUTS: &[(&str, &str)] = &[
+    // Stack overflows in the baseline demangler
+    ("regressions/demangle/nested-array", "deeply nested array parameters"),
+    ("regressions/demangle/nested-function-pointer", "deeply nested function pointer parameters"),
+    ("regressions/demangle/nested-member-function", "deeply nested member function pointers"),
+    ("regressions/demangle/nested-operator", "a conversion to a deeply nested type"),
+    ("regressions/demangle/nested-return-type", "deeply nested function pointer return types"),
+    // Malformed names near past bugs, which never crashed
+    ("edge-cases/demangle/nested-template-args", "template arguments nested past the depth limit"),
+    ("edge-cases/demangle/init-empty", "`__init__` with no variable name"),
+    ("edge-cases/demangle/init-no-class", "`__init__` with no class after the variable"),
+    ("edge-cases/demangle/init-template-args", "template arguments on an `__init__` variable"),
+    ("edge-cases/demangle/init-unclosed-template", "unclosed template arguments after `__init__`"),
+    ("edge-cases/demangle/qualified-name-count", "a qualified name with no components"),
+    ("edge-cases/demangle/qualified-name-short", "fewer qualified name components than counted"),
+    ("edge-cases/demangle/qualified-name-truncated", "a qualified name with no count"),
+    ("edge-cases/demangle/split-at-end", "a `__` separator with nothing after it"),
+    ("edge-cases/demangle/template-args-no-close", "template arguments with no closing `>`"),
+    ("edge-cases/demangle/template-args-unclosed", "template arguments ending after a `,`"),
+];
+
+#[test]
+fn test_fuzz_regressions() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz");
+    // Every input must be labelled
+    for kind in ["regressions", "edge-cases"] {
+        for entry in fs::read_dir(dir.join(kind).join("demangle")).unwrap() {
+            let name = format!("{kind}/demangle/{}", entry.unwrap().file_name().to_string_lossy());
+            assert!(INPUTS.iter().any(|&(input, _)| input == name), "{name} isn't labelled");
+        }
+    }
+    for &(input, _) in INPUTS {
+        let data = fs::read(dir.join(input)).unwrap();
+        let str = String::from_utf8_lossy(&data);
+        for omit_empty_parameters in [false, true] {
+            for mw_extensions in [false, true] {
+                let options = DemangleOptions { omit_empty_parameters, mw_extensions };
+                for encoding in [Encoding::Utf8, Encoding::ShiftJis, Encoding::Escape] {
+                    demangle_bytes(&data, encoding, &options);
+                }
+                let demangled = demangle(&str, &options);
+                demangle_spanned(&str, &options);
+                demangle_tokens(&str, &options);
+                demangle_type_name(&str, &options);
+                demangle_local_static(&str, &options);
+                demangle_affixed(&str, DEFAULT_AFFIXES, &options);
+                if let Some(parts) = demangle_parts(&str, &options) {
+                    assert!(parts.diff(&parts).is_empty(), "{input}");
+                }
+                assert_eq!(explain(&str, &options).demangled, demangled, "{input}");
+                let _ = mangle(&str);
+                if let Some(demangled) = &demangled {
+                    let _ = mangle(demangled);
+                }
+            }
+        }
+    }
+}